@@ -0,0 +1,274 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use serenity::{
+    async_trait,
+    model::id::{ChannelId, GuildId, UserId},
+};
+use snafu::prelude::*;
+use songbird::input::error::Error as InputError;
+use uuid::Uuid;
+
+use super::{QueueEntry, QueuedTrack, Responder, VoiceBackend};
+use crate::{
+    models::command_error::{command_error, CommandError},
+    utils::error_report::ErrorReport,
+};
+
+/// Keeps what commands say and the errors reported to the user instead of sending them.
+#[derive(Default)]
+pub struct FakeResponder {
+    said: Mutex<Vec<String>>,
+    reported: Mutex<Vec<String>>,
+}
+
+impl FakeResponder {
+    pub fn said(&self) -> Vec<String> {
+        self.said.lock().unwrap().clone()
+    }
+
+    pub fn reported(&self) -> Vec<String> {
+        self.reported.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Responder for FakeResponder {
+    async fn say(&self, text: String) {
+        self.said.lock().unwrap().push(text);
+    }
+
+    async fn report(&self, report: &ErrorReport) {
+        self.reported.lock().unwrap().push(report.message.clone());
+    }
+}
+
+#[derive(Default)]
+struct VoiceState {
+    current: Option<ChannelId>,
+    users: HashMap<UserId, ChannelId>,
+    queue: Vec<QueueEntry>,
+    failing: HashSet<String>,
+    uncached: bool,
+    calls: Vec<String>,
+    /// Files playing over the queue, as `play_file` leaves them.
+    mixed: Vec<String>,
+}
+
+/// A single guild's voice state, in memory. Every change is also written down in `calls`, so
+/// tests can check what a command did.
+#[derive(Default)]
+pub struct FakeVoice {
+    state: Mutex<VoiceState>,
+}
+
+impl FakeVoice {
+    pub fn connected_to(self, channel_id: ChannelId) -> FakeVoice {
+        self.state().current = Some(channel_id);
+        self
+    }
+
+    pub fn with_user(self, user_id: UserId, channel_id: ChannelId) -> FakeVoice {
+        self.state().users.insert(user_id, channel_id);
+        self
+    }
+
+    /// Fills the queue with one track per requester.
+    pub fn with_queue(self, requesters: &[Option<UserId>]) -> FakeVoice {
+        self.state()
+            .queue
+            .extend(requesters.iter().map(|&requester| QueueEntry {
+                id: Uuid::new_v4(),
+                requester,
+            }));
+        self
+    }
+
+    /// Acts like the guild is missing from the cache, so nobody's voice state is known.
+    pub fn uncached(self) -> FakeVoice {
+        self.state().uncached = true;
+        self
+    }
+
+    /// Makes playing or queueing this file or URL fail.
+    pub fn failing(self, path: &str) -> FakeVoice {
+        self.state().failing.insert(path.to_string());
+        self
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.state().calls.clone()
+    }
+
+    pub fn mixed(&self) -> Vec<String> {
+        self.state().mixed.clone()
+    }
+
+    pub fn current(&self) -> Option<ChannelId> {
+        self.state().current
+    }
+
+    pub fn queue_requesters(&self) -> Vec<Option<UserId>> {
+        self.state()
+            .queue
+            .iter()
+            .map(|entry| entry.requester)
+            .collect()
+    }
+
+    fn state(&self) -> MutexGuard<'_, VoiceState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Writes the call down, failing it like ffmpeg would if the file is broken.
+    fn source(&self, call: String, path: &str) -> Result<MutexGuard<'_, VoiceState>, InputError> {
+        let mut state = self.state();
+        state.calls.push(call);
+
+        if state.failing.contains(path) {
+            Err(InputError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "corrupt file",
+            )))
+        } else {
+            Ok(state)
+        }
+    }
+}
+
+#[async_trait]
+impl VoiceBackend for FakeVoice {
+    async fn current_channel(&self, _: GuildId) -> Option<ChannelId> {
+        self.state().current
+    }
+
+    async fn user_channel(
+        &self,
+        _: GuildId,
+        user_id: UserId,
+    ) -> Result<Option<ChannelId>, CommandError> {
+        let state = self.state();
+        ensure!(!state.uncached, command_error::GuildNotCachedSnafu);
+
+        Ok(state.users.get(&user_id).copied())
+    }
+
+    async fn listeners(&self, _: GuildId, channel_id: ChannelId) -> Option<usize> {
+        let state = self.state();
+        if state.uncached {
+            return None;
+        }
+
+        let listeners = state
+            .users
+            .values()
+            .filter(|&&channel| channel == channel_id)
+            .count();
+
+        Some(listeners)
+    }
+
+    async fn join(&self, _: GuildId, channel_id: ChannelId) -> Result<(), CommandError> {
+        let mut state = self.state();
+        state.calls.push(format!("join {}", channel_id));
+        state.current = Some(channel_id);
+        Ok(())
+    }
+
+    async fn leave(&self, _: GuildId) -> Result<(), CommandError> {
+        let mut state = self.state();
+        state.calls.push("leave".to_string());
+        state.current = None;
+        Ok(())
+    }
+
+    async fn disconnect(&self, _: GuildId) -> Result<(), CommandError> {
+        let mut state = self.state();
+        state.calls.push("disconnect".to_string());
+        state.current = None;
+        state.queue.clear();
+        Ok(())
+    }
+
+    async fn play_file(&self, _: GuildId, path: &str) -> Result<(), CommandError> {
+        let mut state = self
+            .source(format!("play_file {}", path), path)
+            .map_err(|source| CommandError::Source { source })?;
+        state.mixed.push(path.to_string());
+
+        Ok(())
+    }
+
+    async fn schedule_file(
+        &self,
+        _: GuildId,
+        path: &str,
+        delays: Vec<Duration>,
+    ) -> Result<(), CommandError> {
+        let call = format!("schedule_file {} x{}", path, delays.len());
+        self.source(call, path)
+            .map(|_| ())
+            .map_err(|source| CommandError::Source { source })
+    }
+
+    async fn enqueue_file(&self, _: GuildId, path: &str) -> Result<(), CommandError> {
+        let mut state = self
+            .source(format!("enqueue_file {}", path), path)
+            .map_err(|source| CommandError::Source { source })?;
+        state.queue.push(QueueEntry {
+            id: Uuid::new_v4(),
+            requester: None,
+        });
+        Ok(())
+    }
+
+    async fn enqueue_url(
+        &self,
+        _: GuildId,
+        url: &str,
+        requester: UserId,
+    ) -> Result<QueuedTrack, CommandError> {
+        let mut state = self
+            .source(format!("enqueue_url {}", url), url)
+            .map_err(|source| CommandError::Load { source })?;
+
+        let ahead = state.queue.len();
+        state.queue.push(QueueEntry {
+            id: Uuid::new_v4(),
+            requester: Some(requester),
+        });
+
+        Ok(QueuedTrack {
+            title: url.to_string(),
+            ahead,
+            wait: match ahead {
+                0 => String::new(),
+                1 => "1 track".to_string(),
+                _ => format!("{} tracks", ahead),
+            },
+        })
+    }
+
+    async fn queue(&self, _: GuildId) -> Vec<QueueEntry> {
+        self.state().queue.clone()
+    }
+
+    async fn skip(&self, _: GuildId) -> usize {
+        let mut state = self.state();
+        state.calls.push("skip".to_string());
+        if !state.queue.is_empty() {
+            state.queue.remove(0);
+        }
+        state.queue.len()
+    }
+
+    async fn stop(&self, _: GuildId) {
+        let mut state = self.state();
+        state.calls.push("stop".to_string());
+        state.queue.clear();
+    }
+}
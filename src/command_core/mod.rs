@@ -0,0 +1,102 @@
+#[cfg(test)]
+pub mod fakes;
+pub mod playback;
+pub mod serenity_responder;
+pub mod songbird_backend;
+
+use std::{error::Error, time::Duration};
+
+use serenity::{
+    async_trait,
+    model::id::{ChannelId, GuildId, UserId},
+};
+use uuid::Uuid;
+
+use crate::{models::command_error::CommandError, utils::error_report::ErrorReport};
+
+/// Where a command answers. Prefix commands post in the channel, slash commands edit their
+/// deferred response. Commands return their errors, the caller hands them to `report`.
+#[async_trait]
+pub trait Responder: Send + Sync {
+    async fn say(&self, text: String);
+    /// Shows why the command failed.
+    async fn report(&self, report: &ErrorReport);
+}
+
+/// Logs a failed command under a new error ID and tells the user about it.
+pub async fn report_failure(
+    responder: &dyn Responder,
+    command_name: &str,
+    error: &(dyn Error + Send + Sync + 'static),
+) {
+    responder
+        .report(&ErrorReport::new(command_name, error))
+        .await;
+}
+
+/// A track in a guild's queue, as far as the commands are concerned.
+#[derive(Clone, Debug)]
+pub struct QueueEntry {
+    pub id: Uuid,
+    /// `None` for tracks queued by something other than `play`, such as sirens.
+    pub requester: Option<UserId>,
+}
+
+/// Where `enqueue_url` put a track.
+#[derive(Clone, Debug)]
+pub struct QueuedTrack {
+    pub title: String,
+    /// Number of tracks that play before it.
+    pub ahead: usize,
+    /// Estimate of how long until it plays, empty when nothing is ahead.
+    pub wait: String,
+}
+
+/// The voice side of the commands: who is in which channel, joining and leaving, and the
+/// guild's queue.
+#[async_trait]
+pub trait VoiceBackend: Send + Sync {
+    /// The channel the bot is connected to in the guild.
+    async fn current_channel(&self, guild_id: GuildId) -> Option<ChannelId>;
+    /// The channel a member is in. Fails when the guild isn't cached, since then nobody's
+    /// channel is known.
+    async fn user_channel(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<Option<ChannelId>, CommandError>;
+    /// Number of people in a channel, not counting bots. `None` when it can't be told.
+    async fn listeners(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<usize>;
+
+    async fn join(&self, guild_id: GuildId, channel_id: ChannelId) -> Result<(), CommandError>;
+    /// Leaves the channel but keeps the guild's call, so it can join again right away.
+    async fn leave(&self, guild_id: GuildId) -> Result<(), CommandError>;
+    /// Stops everything and leaves, dropping the guild's call.
+    async fn disconnect(&self, guild_id: GuildId) -> Result<(), CommandError>;
+
+    /// Plays a file over whatever is playing, outside of the queue.
+    async fn play_file(&self, guild_id: GuildId, path: &str) -> Result<(), CommandError>;
+    /// Plays a file once per delay, in the background, over whatever is playing. Fails right
+    /// away if the file can't be opened.
+    async fn schedule_file(
+        &self,
+        guild_id: GuildId,
+        path: &str,
+        delays: Vec<Duration>,
+    ) -> Result<(), CommandError>;
+    async fn enqueue_file(&self, guild_id: GuildId, path: &str) -> Result<(), CommandError>;
+    /// Queues a URL for yt-dlp under the requester's name.
+    async fn enqueue_url(
+        &self,
+        guild_id: GuildId,
+        url: &str,
+        requester: UserId,
+    ) -> Result<QueuedTrack, CommandError>;
+
+    /// The guild's queue, starting with the track that is playing.
+    async fn queue(&self, guild_id: GuildId) -> Vec<QueueEntry>;
+    /// Skips the track that is playing and returns how many are left in the queue.
+    async fn skip(&self, guild_id: GuildId) -> usize;
+    /// Stops the track that is playing and clears the queue.
+    async fn stop(&self, guild_id: GuildId);
+}
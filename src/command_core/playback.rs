@@ -0,0 +1,654 @@
+use std::{collections::HashMap, time::Duration};
+
+use rand::Rng;
+use serenity::{
+    model::id::{ChannelId, GuildId},
+    prelude::{Mentionable, RwLock},
+};
+use snafu::prelude::*;
+use tracing::warn;
+
+use super::{Responder, VoiceBackend};
+use crate::{
+    models::{
+        command_error::{command_error, CommandError},
+        guild_settings::GuildSettings,
+        skip_votes::{votes_needed, SkipVotes},
+    },
+    utils::{invocation::Invocation, parse::get_repeat_count},
+};
+
+/// How long `ba` waits between joining and leaving.
+const BA_HOP: Duration = Duration::from_millis(500);
+
+/// Makes sure the bot is in a voice channel for a playback command. If it isn't, it joins the
+/// author's, unless the guild turned `auto_join` off.
+pub async fn ensure_channel(
+    voice: &dyn VoiceBackend,
+    invocation: &Invocation,
+    settings: &GuildSettings,
+) -> Result<(), CommandError> {
+    if voice.current_channel(invocation.guild_id).await.is_some() {
+        return Ok(());
+    }
+    ensure!(settings.auto_join, command_error::NotInVoiceSnafu);
+
+    join_author(voice, invocation).await.map(|_| ())
+}
+
+async fn join_author(
+    voice: &dyn VoiceBackend,
+    invocation: &Invocation,
+) -> Result<ChannelId, CommandError> {
+    let channel = voice
+        .user_channel(invocation.guild_id, invocation.author_id)
+        .await?
+        .context(command_error::AuthorNotInVoiceSnafu)?;
+    voice.join(invocation.guild_id, channel).await?;
+
+    Ok(channel)
+}
+
+pub async fn join(
+    voice: &dyn VoiceBackend,
+    responder: &dyn Responder,
+    invocation: &Invocation,
+) -> Result<(), CommandError> {
+    let channel = join_author(voice, invocation).await?;
+    responder.say(format!("Joined {}", channel.mention())).await;
+
+    Ok(())
+}
+
+pub async fn leave(
+    voice: &dyn VoiceBackend,
+    responder: &dyn Responder,
+    invocation: &Invocation,
+) -> Result<(), CommandError> {
+    ensure!(
+        voice.current_channel(invocation.guild_id).await.is_some(),
+        command_error::NotInVoiceSnafu
+    );
+
+    voice.disconnect(invocation.guild_id).await?;
+    responder.say("Left voice channel".to_string()).await;
+
+    Ok(())
+}
+
+/// Hops in and out of the author's channel, ending up in it. Does nothing if the author isn't
+/// in one.
+pub async fn ba(
+    voice: &dyn VoiceBackend,
+    invocation: &Invocation,
+    settings: &GuildSettings,
+    count: Option<i32>,
+) -> Result<(), CommandError> {
+    let guild_id = invocation.guild_id;
+    let channel = match voice.user_channel(guild_id, invocation.author_id).await? {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+
+    let repeat_count = get_repeat_count(count, 6, settings.max_repeat);
+
+    // A hop that fails is only worth a log line, the next one is worth a try anyway.
+    for i in 0..repeat_count {
+        if i > 0 {
+            tokio::time::sleep(BA_HOP).await;
+        }
+
+        let hop = if i % 2 == 0 {
+            voice.join(guild_id, channel).await
+        } else {
+            voice.leave(guild_id).await
+        };
+        if let Err(why) = hop {
+            warn!("Err hopping: {:?}", why);
+        }
+    }
+
+    voice.join(guild_id, channel).await
+}
+
+/// Plays a sound over whatever is playing.
+pub async fn sound(
+    voice: &dyn VoiceBackend,
+    invocation: &Invocation,
+    settings: &GuildSettings,
+    path: &str,
+) -> Result<(), CommandError> {
+    ensure_channel(voice, invocation, settings).await?;
+
+    voice.play_file(invocation.guild_id, path).await
+}
+
+/// Plays a sound over and over, a little apart each time.
+pub async fn spam(
+    voice: &dyn VoiceBackend,
+    invocation: &Invocation,
+    settings: &GuildSettings,
+    path: &str,
+    count: Option<i32>,
+) -> Result<(), CommandError> {
+    let repeat_count = get_repeat_count(count, 10, settings.max_repeat);
+    let delays = {
+        let mut rng = rand::thread_rng();
+        (0..repeat_count)
+            .map(|_| Duration::from_millis(rng.gen_range(25..125)))
+            .collect()
+    };
+
+    ensure_channel(voice, invocation, settings).await?;
+
+    voice.schedule_file(invocation.guild_id, path, delays).await
+}
+
+/// Queues the two halves of a siren one after the other, starting with the left one.
+pub async fn siren(
+    voice: &dyn VoiceBackend,
+    invocation: &Invocation,
+    settings: &GuildSettings,
+    (left, right): (&str, &str),
+    count: Option<i32>,
+) -> Result<(), CommandError> {
+    ensure_channel(voice, invocation, settings).await?;
+
+    let repeat_count = get_repeat_count(count, 10, settings.max_repeat);
+    for i in 0..repeat_count {
+        let path = if i % 2 == 0 { left } else { right };
+        voice.enqueue_file(invocation.guild_id, path).await?;
+    }
+
+    Ok(())
+}
+
+/// Queues a single track and says when it will play.
+pub async fn play(
+    voice: &dyn VoiceBackend,
+    responder: &dyn Responder,
+    invocation: &Invocation,
+    settings: &GuildSettings,
+    url: &str,
+) -> Result<(), CommandError> {
+    ensure_channel(voice, invocation, settings).await?;
+
+    let track = voice
+        .enqueue_url(invocation.guild_id, url, invocation.author_id)
+        .await?;
+
+    let text = if track.ahead == 0 {
+        format!("Playing `{}`", track.title)
+    } else {
+        format!(
+            "Queued `{}` at position {}, playing in about {}",
+            track.title, track.ahead, track.wait
+        )
+    };
+    responder.say(text).await;
+
+    Ok(())
+}
+
+/// Skips right away for the requester and DJs, and counts a vote for anyone else.
+pub async fn skip(
+    voice: &dyn VoiceBackend,
+    responder: &dyn Responder,
+    invocation: &Invocation,
+    settings: &GuildSettings,
+    is_dj: bool,
+    votes: &RwLock<HashMap<GuildId, SkipVotes>>,
+) -> Result<(), CommandError> {
+    let guild_id = invocation.guild_id;
+
+    let channel = voice
+        .current_channel(guild_id)
+        .await
+        .context(command_error::NotInVoiceSnafu)?;
+    let current = voice
+        .queue(guild_id)
+        .await
+        .into_iter()
+        .next()
+        .context(command_error::NothingPlayingSnafu)?;
+
+    if current.requester != Some(invocation.author_id) && !is_dj {
+        ensure!(
+            voice.user_channel(guild_id, invocation.author_id).await? == Some(channel),
+            command_error::NotListeningSnafu
+        );
+
+        let listeners = voice
+            .listeners(guild_id, channel)
+            .await
+            .context(command_error::GuildNotCachedSnafu)?;
+        let needed = votes_needed(listeners, settings.skip_vote_percent);
+        // Held for the vote only, not across the voice calls and the reply.
+        let votes = votes
+            .write()
+            .await
+            .entry(guild_id)
+            .or_default()
+            .vote(current.id, invocation.author_id);
+        if votes < needed {
+            responder
+                .say(format!("Voted to skip: {}/{}", votes, needed))
+                .await;
+            return Ok(());
+        }
+    }
+
+    let left = voice.skip(guild_id).await;
+    responder
+        .say(format!("Song skipped: {} in queue.", left))
+        .await;
+
+    Ok(())
+}
+
+/// Clears the queue, if everything in it was asked for by the author or they are a DJ.
+pub async fn stop(
+    voice: &dyn VoiceBackend,
+    responder: &dyn Responder,
+    invocation: &Invocation,
+    is_dj: bool,
+) -> Result<(), CommandError> {
+    let guild_id = invocation.guild_id;
+
+    ensure!(
+        voice.current_channel(guild_id).await.is_some(),
+        command_error::NotInVoiceSnafu
+    );
+
+    let requested_all = voice
+        .queue(guild_id)
+        .await
+        .iter()
+        .all(|entry| entry.requester == Some(invocation.author_id));
+    ensure!(
+        requested_all || is_dj,
+        command_error::PermissionDeniedSnafu {
+            reason: "Only a DJ can stop other people's songs, vote with `.skip` instead",
+        }
+    );
+
+    voice.stop(guild_id).await;
+    responder.say("Queue cleared.".to_string()).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::model::id::UserId;
+
+    use super::*;
+    use crate::command_core::{
+        fakes::{FakeResponder, FakeVoice},
+        report_failure,
+    };
+
+    const AUTHOR: UserId = UserId(1);
+    const OTHER: UserId = UserId(2);
+    const CHANNEL: ChannelId = ChannelId(10);
+
+    fn invocation(author_id: UserId) -> Invocation {
+        Invocation {
+            guild_id: GuildId(100),
+            channel_id: ChannelId(200),
+            author_id,
+        }
+    }
+
+    #[tokio::test]
+    async fn sound_joins_the_author() {
+        let voice = FakeVoice::default().with_user(AUTHOR, CHANNEL);
+
+        sound(
+            &voice,
+            &invocation(AUTHOR),
+            &GuildSettings::default(),
+            "a.ogg",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(voice.calls(), ["join 10", "play_file a.ogg"]);
+    }
+
+    #[tokio::test]
+    async fn sound_plays_over_the_queue() {
+        let voice = FakeVoice::default()
+            .connected_to(CHANNEL)
+            .with_queue(&[Some(OTHER), Some(OTHER)]);
+
+        sound(
+            &voice,
+            &invocation(AUTHOR),
+            &GuildSettings::default(),
+            "a.ogg",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(voice.mixed(), ["a.ogg"]);
+        assert_eq!(voice.queue_requesters(), [Some(OTHER), Some(OTHER)]);
+    }
+
+    #[tokio::test]
+    async fn sound_fails_when_it_cannot_join() {
+        let settings = GuildSettings {
+            auto_join: false,
+            ..GuildSettings::default()
+        };
+        let voice = FakeVoice::default().with_user(AUTHOR, CHANNEL);
+        let result = sound(&voice, &invocation(AUTHOR), &settings, "a.ogg").await;
+
+        assert!(matches!(result, Err(CommandError::NotInVoice)));
+        assert!(voice.calls().is_empty());
+
+        let voice = FakeVoice::default();
+        let result = sound(
+            &voice,
+            &invocation(AUTHOR),
+            &GuildSettings::default(),
+            "a.ogg",
+        )
+        .await;
+
+        assert!(matches!(result, Err(CommandError::AuthorNotInVoice)));
+    }
+
+    /// A guild missing from the cache has no voice states, so the author's channel can't be
+    /// told. That gets its own reply rather than claiming they aren't in one.
+    #[tokio::test]
+    async fn uncached_guild_gets_a_reply() {
+        let voice = FakeVoice::default().uncached();
+        let responder = FakeResponder::default();
+        let result = sound(
+            &voice,
+            &invocation(AUTHOR),
+            &GuildSettings::default(),
+            "a.ogg",
+        )
+        .await;
+        report_failure(&responder, "sound", &result.unwrap_err()).await;
+
+        assert_eq!(
+            responder.reported(),
+            ["This server isn't loaded yet, try again in a moment"]
+        );
+        assert!(responder.said().is_empty());
+    }
+
+    #[tokio::test]
+    async fn corrupt_sound_gets_a_reply() {
+        let voice = FakeVoice::default().connected_to(CHANNEL).failing("a.ogg");
+        let responder = FakeResponder::default();
+        let settings = GuildSettings::default();
+        let result = spam(&voice, &invocation(AUTHOR), &settings, "a.ogg", Some(3)).await;
+        report_failure(&responder, "spam", &result.unwrap_err()).await;
+
+        assert_eq!(responder.reported(), ["Could not play that sound"]);
+        assert_eq!(voice.calls(), ["schedule_file a.ogg x3"]);
+    }
+
+    #[tokio::test]
+    async fn spam_repeats_up_to_the_limit() {
+        let voice = FakeVoice::default().connected_to(CHANNEL);
+        let settings = GuildSettings::default();
+
+        for count in [Some(3), None, Some(50)] {
+            spam(&voice, &invocation(AUTHOR), &settings, "a.ogg", count)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            voice.calls(),
+            [
+                "schedule_file a.ogg x3",
+                "schedule_file a.ogg x10",
+                "schedule_file a.ogg x1"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn siren_alternates_halves() {
+        let voice = FakeVoice::default().connected_to(CHANNEL);
+
+        siren(
+            &voice,
+            &invocation(AUTHOR),
+            &GuildSettings::default(),
+            ("l.ogg", "r.ogg"),
+            Some(3),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            voice.calls(),
+            [
+                "enqueue_file l.ogg",
+                "enqueue_file r.ogg",
+                "enqueue_file l.ogg"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn siren_stops_at_the_first_broken_file() {
+        let voice = FakeVoice::default().connected_to(CHANNEL).failing("r.ogg");
+
+        let result = siren(
+            &voice,
+            &invocation(AUTHOR),
+            &GuildSettings::default(),
+            ("l.ogg", "r.ogg"),
+            Some(4),
+        )
+        .await;
+
+        assert!(matches!(result, Err(CommandError::Source { .. })));
+        assert_eq!(voice.calls(), ["enqueue_file l.ogg", "enqueue_file r.ogg"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ba_hops_and_ends_up_in_the_channel() {
+        let voice = FakeVoice::default().with_user(AUTHOR, CHANNEL);
+        let started = tokio::time::Instant::now();
+
+        ba(
+            &voice,
+            &invocation(AUTHOR),
+            &GuildSettings::default(),
+            Some(3),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(voice.calls(), ["join 10", "leave", "join 10", "join 10"]);
+        assert_eq!(started.elapsed(), BA_HOP * 2);
+        assert_eq!(voice.current(), Some(CHANNEL));
+    }
+
+    #[tokio::test]
+    async fn ba_needs_the_author_in_a_channel() {
+        let voice = FakeVoice::default();
+
+        ba(&voice, &invocation(AUTHOR), &GuildSettings::default(), None)
+            .await
+            .unwrap();
+
+        assert!(voice.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn play_says_where_the_track_went() {
+        let voice = FakeVoice::default()
+            .with_user(AUTHOR, CHANNEL)
+            .failing("https://broken");
+        let responder = FakeResponder::default();
+        let settings = GuildSettings::default();
+
+        for url in ["https://first", "https://second"] {
+            play(&voice, &responder, &invocation(AUTHOR), &settings, url)
+                .await
+                .unwrap();
+        }
+        let result = play(
+            &voice,
+            &responder,
+            &invocation(AUTHOR),
+            &settings,
+            "https://broken",
+        )
+        .await;
+
+        assert!(matches!(result, Err(CommandError::Load { .. })));
+        assert_eq!(
+            responder.said(),
+            [
+                "Playing `https://first`",
+                "Queued `https://second` at position 1, playing in about 1 track",
+            ]
+        );
+        assert_eq!(voice.queue_requesters(), [Some(AUTHOR), Some(AUTHOR)]);
+    }
+
+    #[tokio::test]
+    async fn requester_and_dj_skip_without_a_vote() {
+        let settings = GuildSettings::default();
+        let voice = FakeVoice::default().connected_to(CHANNEL).with_queue(&[
+            Some(AUTHOR),
+            Some(AUTHOR),
+            Some(AUTHOR),
+        ]);
+        let responder = FakeResponder::default();
+        let votes = RwLock::default();
+
+        skip(
+            &voice,
+            &responder,
+            &invocation(AUTHOR),
+            &settings,
+            false,
+            &votes,
+        )
+        .await
+        .unwrap();
+        skip(
+            &voice,
+            &responder,
+            &invocation(OTHER),
+            &settings,
+            true,
+            &votes,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            responder.said(),
+            ["Song skipped: 2 in queue.", "Song skipped: 1 in queue."]
+        );
+    }
+
+    #[tokio::test]
+    async fn others_vote_to_skip() {
+        let settings = GuildSettings::default();
+        let third = UserId(3);
+        let voice = FakeVoice::default()
+            .connected_to(CHANNEL)
+            .with_user(AUTHOR, CHANNEL)
+            .with_user(OTHER, CHANNEL)
+            .with_user(third, CHANNEL)
+            .with_queue(&[Some(AUTHOR), None]);
+        let responder = FakeResponder::default();
+        let votes = RwLock::default();
+
+        let outsider = skip(
+            &voice,
+            &responder,
+            &invocation(UserId(4)),
+            &settings,
+            false,
+            &votes,
+        )
+        .await;
+        assert!(matches!(outsider, Err(CommandError::NotListening)));
+
+        for voter in [OTHER, OTHER, third] {
+            skip(
+                &voice,
+                &responder,
+                &invocation(voter),
+                &settings,
+                false,
+                &votes,
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            responder.said(),
+            [
+                "Voted to skip: 1/2",
+                "Voted to skip: 1/2",
+                "Song skipped: 1 in queue.",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn skip_needs_something_playing() {
+        let settings = GuildSettings::default();
+        let responder = FakeResponder::default();
+        let votes = RwLock::default();
+
+        let voice = FakeVoice::default();
+        let result = skip(
+            &voice,
+            &responder,
+            &invocation(AUTHOR),
+            &settings,
+            false,
+            &votes,
+        )
+        .await;
+        assert!(matches!(result, Err(CommandError::NotInVoice)));
+
+        let voice = FakeVoice::default().connected_to(CHANNEL);
+        let result = skip(
+            &voice,
+            &responder,
+            &invocation(AUTHOR),
+            &settings,
+            false,
+            &votes,
+        )
+        .await;
+        assert!(matches!(result, Err(CommandError::NothingPlaying)));
+    }
+
+    #[tokio::test]
+    async fn only_djs_stop_other_peoples_tracks() {
+        let voice = FakeVoice::default()
+            .connected_to(CHANNEL)
+            .with_queue(&[Some(AUTHOR), Some(OTHER)]);
+        let responder = FakeResponder::default();
+
+        let result = stop(&voice, &responder, &invocation(AUTHOR), false).await;
+        assert!(matches!(result, Err(CommandError::PermissionDenied { .. })));
+        assert_eq!(voice.queue_requesters().len(), 2);
+
+        stop(&voice, &responder, &invocation(AUTHOR), true)
+            .await
+            .unwrap();
+        assert!(voice.queue_requesters().is_empty());
+        assert_eq!(responder.said(), ["Queue cleared."]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serenity::{
+    async_trait,
+    model::{
+        application::interaction::application_command::ApplicationCommandInteraction,
+        prelude::Message,
+    },
+    prelude::Context,
+};
+
+use super::Responder;
+use crate::utils::{
+    checks::{check_msg, check_result},
+    error_report::ErrorReport,
+};
+
+/// Answers a prefix command in its channel.
+pub struct MessageResponder<'a> {
+    ctx: &'a Context,
+    msg: &'a Message,
+}
+
+impl<'a> MessageResponder<'a> {
+    pub fn new(ctx: &'a Context, msg: &'a Message) -> MessageResponder<'a> {
+        MessageResponder { ctx, msg }
+    }
+}
+
+#[async_trait]
+impl Responder for MessageResponder<'_> {
+    async fn say(&self, text: String) {
+        check_msg(self.msg.channel_id.say(self.ctx, text).await);
+    }
+
+    async fn report(&self, report: &ErrorReport) {
+        check_msg(
+            self.msg
+                .channel_id
+                .send_message(self.ctx, |m| {
+                    m.reference_message(self.msg).embed(|e| report.embed(e))
+                })
+                .await,
+        );
+    }
+}
+
+/// Answers a deferred slash command. The first answer fills in the deferred response and the
+/// others are sent as followups.
+pub struct InteractionResponder<'a> {
+    ctx: &'a Context,
+    command: &'a ApplicationCommandInteraction,
+    answered: AtomicBool,
+}
+
+impl<'a> InteractionResponder<'a> {
+    pub fn new(
+        ctx: &'a Context,
+        command: &'a ApplicationCommandInteraction,
+    ) -> InteractionResponder<'a> {
+        InteractionResponder {
+            ctx,
+            command,
+            answered: AtomicBool::new(false),
+        }
+    }
+
+    /// Fills in the deferred response if the command had nothing to say, so Discord doesn't
+    /// leave it loading.
+    pub async fn finish(&self) {
+        if !self.answered.load(Ordering::SeqCst) {
+            self.answer("Done".to_string()).await;
+        }
+    }
+
+    async fn answer(&self, text: String) {
+        if self.answered.swap(true, Ordering::SeqCst) {
+            check_result(
+                self.command
+                    .create_followup_message(self.ctx, |m| m.content(text))
+                    .await,
+                "Error sending followup",
+            );
+        } else {
+            check_result(
+                self.command
+                    .edit_original_interaction_response(self.ctx, |r| r.content(text))
+                    .await,
+                "Error answering slash command",
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl Responder for InteractionResponder<'_> {
+    async fn say(&self, text: String) {
+        self.answer(text).await;
+    }
+
+    async fn report(&self, report: &ErrorReport) {
+        if self.answered.swap(true, Ordering::SeqCst) {
+            check_result(
+                self.command
+                    .create_followup_message(self.ctx, |m| m.embed(|e| report.embed(e)))
+                    .await,
+                "Error sending followup",
+            );
+        } else {
+            check_result(
+                self.command
+                    .edit_original_interaction_response(self.ctx, |r| r.embed(|e| report.embed(e)))
+                    .await,
+                "Error answering slash command",
+            );
+        }
+    }
+}
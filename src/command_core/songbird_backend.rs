@@ -0,0 +1,230 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use serenity::{
+    async_trait,
+    model::id::{ChannelId, GuildId, UserId},
+    prelude::{Context, Mutex},
+};
+use snafu::prelude::*;
+use songbird::{
+    input::{self, Input, Restartable},
+    Call, Songbird,
+};
+use tracing::warn;
+
+use super::{QueueEntry, QueuedTrack, VoiceBackend};
+use crate::{
+    commands::queue::estimate_wait,
+    event_handlers::track_loop::watch_loop,
+    models::{
+        command_error::{command_error, CommandError},
+        track_request::{attach_request, get_request},
+    },
+    storage::get_guild_settings,
+    utils::{
+        probe::probe_duration,
+        schedule::schedule_playback,
+        voice::{count_listeners, disconnect},
+    },
+};
+
+/// The voice backend of the running bot, on top of songbird and the serenity cache.
+pub struct SongbirdBackend<'a> {
+    ctx: &'a Context,
+}
+
+impl<'a> SongbirdBackend<'a> {
+    pub fn new(ctx: &'a Context) -> SongbirdBackend<'a> {
+        SongbirdBackend { ctx }
+    }
+
+    async fn manager(&self) -> Arc<Songbird> {
+        songbird::get(self.ctx)
+            .await
+            .expect("Songbird Voice client placed in at initialisation.")
+            .clone()
+    }
+
+    async fn call(&self, guild_id: GuildId) -> Result<Arc<Mutex<Call>>, CommandError> {
+        self.manager()
+            .await
+            .get(guild_id)
+            .context(command_error::NotInVoiceSnafu)
+    }
+}
+
+#[async_trait]
+impl VoiceBackend for SongbirdBackend<'_> {
+    async fn current_channel(&self, guild_id: GuildId) -> Option<ChannelId> {
+        let handler_lock = self.manager().await.get(guild_id)?;
+        let channel = handler_lock.lock().await.current_channel();
+
+        channel.map(|channel| ChannelId(channel.0))
+    }
+
+    async fn user_channel(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<Option<ChannelId>, CommandError> {
+        self.ctx
+            .cache
+            .guild_field(guild_id, |guild| {
+                guild
+                    .voice_states
+                    .get(&user_id)
+                    .and_then(|voice_state| voice_state.channel_id)
+            })
+            .context(command_error::GuildNotCachedSnafu)
+    }
+
+    async fn listeners(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<usize> {
+        count_listeners(self.ctx, guild_id, channel_id)
+    }
+
+    async fn join(&self, guild_id: GuildId, channel_id: ChannelId) -> Result<(), CommandError> {
+        let (_, success) = self.manager().await.join(guild_id, channel_id).await;
+
+        success.context(command_error::JoinSnafu)
+    }
+
+    async fn leave(&self, guild_id: GuildId) -> Result<(), CommandError> {
+        let handler_lock = self.call(guild_id).await?;
+        let result = handler_lock.lock().await.leave().await;
+
+        result.context(command_error::LeaveSnafu)
+    }
+
+    async fn disconnect(&self, guild_id: GuildId) -> Result<(), CommandError> {
+        disconnect(self.ctx, guild_id)
+            .await
+            .context(command_error::LeaveSnafu)
+    }
+
+    async fn play_file(&self, guild_id: GuildId, path: &str) -> Result<(), CommandError> {
+        let handler_lock = self.call(guild_id).await?;
+        let source = ffmpeg(path).await?;
+
+        // Not `play_only_source`, which would stop the queue's current track too.
+        handler_lock.lock().await.play_source(source);
+        Ok(())
+    }
+
+    async fn schedule_file(
+        &self,
+        guild_id: GuildId,
+        path: &str,
+        delays: Vec<Duration>,
+    ) -> Result<(), CommandError> {
+        let handler_lock = self.call(guild_id).await?;
+        // Probes the file right away, so a broken one is reported to the user instead of only
+        // failing in the background. Cheaper than starting ffmpeg on it.
+        ensure!(
+            probe_duration(Path::new(path)).await.is_some(),
+            command_error::UnreadableSoundSnafu
+        );
+        let path = path.to_string();
+
+        schedule_playback(delays, move || {
+            let handler_lock = handler_lock.clone();
+            let path = path.clone();
+            async move {
+                match ffmpeg(&path).await {
+                    Ok(source) => {
+                        handler_lock.lock().await.play_source(source);
+                    }
+                    Err(why) => warn!("Err starting source: {:?}", why),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn enqueue_file(&self, guild_id: GuildId, path: &str) -> Result<(), CommandError> {
+        let handler_lock = self.call(guild_id).await?;
+        let source = ffmpeg(path).await?;
+
+        handler_lock.lock().await.enqueue_source(source);
+        Ok(())
+    }
+
+    /// Queues the URL at the guild's music volume and under its loop mode.
+    async fn enqueue_url(
+        &self,
+        guild_id: GuildId,
+        url: &str,
+        requester: UserId,
+    ) -> Result<QueuedTrack, CommandError> {
+        let handler_lock = self.call(guild_id).await?;
+
+        // Lazy restartable sources only run yt-dlp for metadata now, and don't pay for decoding
+        // tracks which aren't live yet.
+        let source = Restartable::ytdl(url.to_string(), true)
+            .await
+            .context(command_error::LoadSnafu)?;
+        let track = handler_lock.lock().await.enqueue_source(source.into());
+
+        let volume = get_guild_settings(self.ctx, guild_id).await.music_volume as f32 / 100.0;
+        let _ = track.set_volume(volume);
+        attach_request(&track, requester, url).await;
+        watch_loop(self.ctx, guild_id, &handler_lock, &track).await;
+
+        let queued = handler_lock.lock().await.queue().current_queue();
+        let ahead = &queued[..queued.len().saturating_sub(1)];
+
+        Ok(QueuedTrack {
+            title: track
+                .metadata()
+                .title
+                .clone()
+                .unwrap_or_else(|| url.to_string()),
+            ahead: ahead.len(),
+            wait: if ahead.is_empty() {
+                String::new()
+            } else {
+                estimate_wait(ahead).await
+            },
+        })
+    }
+
+    async fn queue(&self, guild_id: GuildId) -> Vec<QueueEntry> {
+        let tracks = match self.call(guild_id).await {
+            Ok(handler_lock) => handler_lock.lock().await.queue().current_queue(),
+            Err(_) => return Vec::new(),
+        };
+
+        let mut entries = Vec::with_capacity(tracks.len());
+        for track in tracks {
+            entries.push(QueueEntry {
+                id: track.uuid(),
+                requester: get_request(&track).await.map(|request| request.requester),
+            });
+        }
+        entries
+    }
+
+    async fn skip(&self, guild_id: GuildId) -> usize {
+        match self.call(guild_id).await {
+            Ok(handler_lock) => {
+                let queue = handler_lock.lock().await.queue().clone();
+                let _ = queue.skip();
+                queue.len()
+            }
+            Err(_) => 0,
+        }
+    }
+
+    async fn stop(&self, guild_id: GuildId) {
+        if let Ok(handler_lock) = self.call(guild_id).await {
+            let mut handler = handler_lock.lock().await;
+            handler.stop();
+            handler.queue().stop();
+        }
+    }
+}
+
+async fn ffmpeg(path: &str) -> Result<Input, CommandError> {
+    input::ffmpeg(path)
+        .await
+        .context(command_error::SourceSnafu)
+}
@@ -4,51 +4,31 @@ use serenity::{
         CommandResult,
     },
     model::prelude::Message,
-    prelude::{Context, Mentionable},
+    prelude::Context,
 };
+use snafu::prelude::*;
 
-use crate::utils::checks::check_msg;
+use crate::{
+    command_core::{
+        playback, serenity_responder::MessageResponder, songbird_backend::SongbirdBackend,
+        Responder,
+    },
+    commands::{permissions::ALLOWED_CHECK, settings::GROUPENABLED_CHECK},
+    models::command_error::command_error,
+    utils::invocation::Invocation,
+};
 
 #[group]
+#[checks(GroupEnabled, Allowed)]
 #[commands(join, leave, ping)]
 pub struct Essentials;
 
 #[command]
 #[only_in(guilds)]
 pub async fn join(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
-
-    let channel_id = guild
-        .voice_states
-        .get(&msg.author.id)
-        .and_then(|voice_state| voice_state.channel_id);
-
-    let connect_to = match channel_id {
-        Some(channel) => channel,
-        None => {
-            check_msg(msg.reply(ctx, "Not in a voice channel").await);
-
-            return Ok(());
-        }
-    };
-
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
-
-    let (_, success) = manager.join(guild_id, connect_to).await;
-
-    if let Ok(_channel) = success {
-        check_msg(
-            msg.channel_id
-                .say(ctx, &format!("Joined {}", connect_to.mention()))
-                .await,
-        );
-    } else {
-        check_msg(msg.channel_id.say(ctx, "Error joining the channel").await);
-    }
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let responder = MessageResponder::new(ctx, msg);
+    playback::join(&SongbirdBackend::new(ctx), &responder, &invocation).await?;
 
     Ok(())
 }
@@ -56,31 +36,20 @@ pub async fn join(ctx: &Context, msg: &Message) -> CommandResult {
 #[command]
 #[only_in(guilds)]
 pub async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
-    let has_handler = manager.get(guild_id).is_some();
-
-    if has_handler {
-        if let Err(e) = manager.remove(guild_id).await {
-            check_msg(msg.channel_id.say(ctx, format!("Failed: {:?}", e)).await);
-        }
-
-        check_msg(msg.channel_id.say(ctx, "Left voice channel").await);
-    } else {
-        check_msg(msg.reply(ctx, "Not in a voice channel").await);
-    }
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let responder = MessageResponder::new(ctx, msg);
+    playback::leave(&SongbirdBackend::new(ctx), &responder, &invocation).await?;
 
     Ok(())
 }
 
 #[command]
 pub async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
-    check_msg(msg.channel_id.say(ctx, "Pong!").await);
+    run_ping(&MessageResponder::new(ctx, msg)).await;
 
     Ok(())
 }
+
+pub(crate) async fn run_ping(responder: &dyn Responder) {
+    responder.say("Pong!".to_string()).await;
+}
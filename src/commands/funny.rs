@@ -1,5 +1,3 @@
-use std::time::Duration;
-
 use serenity::{
     framework::standard::{
         macros::{command, group},
@@ -8,56 +6,36 @@ use serenity::{
     model::prelude::Message,
     prelude::Context,
 };
-
-use crate::utils::{checks::check_result, parse::get_repeat_count};
+use snafu::prelude::*;
+
+use crate::{
+    command_core::{playback, songbird_backend::SongbirdBackend},
+    commands::{permissions::ALLOWED_CHECK, settings::GROUPENABLED_CHECK},
+    models::command_error::{command_error, CommandError},
+    storage::get_guild_settings,
+    utils::invocation::Invocation,
+};
 
 #[group]
+#[checks(GroupEnabled, Allowed)]
 #[commands(ba)]
 pub struct Funny;
 
 #[command]
 #[only_in(guilds)]
-pub async fn ba(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
-
-    let channel_id = guild
-        .voice_states
-        .get(&msg.author.id)
-        .and_then(|voice_state| voice_state.channel_id);
-
-    let connect_to = match channel_id {
-        Some(channel) => channel,
-        None => {
-            return Ok(());
-        }
-    };
-
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
-
-    let handler_lock = manager.get_or_insert(guild_id);
-
-    let repeat_count = get_repeat_count(args, 6);
-
-    for i in 0..repeat_count {
-        let mut handler = handler_lock.lock().await;
-
-        if i > 0 {
-            tokio::time::sleep(Duration::from_millis(500)).await;
-        }
-
-        if i % 2 == 0 {
-            check_result(handler.join(connect_to).await, "Could not join");
-        } else {
-            check_result(handler.leave().await, "Could not leave");
-        }
-    }
-
-    let mut handler = handler_lock.lock().await;
-    check_result(handler.join(connect_to).await, "Could not join");
+pub async fn ba(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let count = args.single::<i32>().ok();
+    run_ba(ctx, &invocation, count).await?;
 
     Ok(())
 }
+
+pub(crate) async fn run_ba(
+    ctx: &Context,
+    invocation: &Invocation,
+    count: Option<i32>,
+) -> Result<(), CommandError> {
+    let settings = get_guild_settings(ctx, invocation.guild_id).await;
+    playback::ba(&SongbirdBackend::new(ctx), invocation, &settings, count).await
+}
@@ -0,0 +1,127 @@
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::Message,
+    prelude::Context,
+};
+use snafu::prelude::*;
+
+use crate::{
+    commands::{permissions::ALLOWED_CHECK, settings::GROUPENABLED_CHECK},
+    models::{
+        command_error::{command_error, CommandError},
+        user_preferences::UserPreferences,
+    },
+    storage::{get_storage, get_user_preferences},
+    utils::{checks::check_msg, parse::get_sound_path},
+};
+
+#[group]
+#[prefixes("greet")]
+#[only_in(guilds)]
+#[checks(GroupEnabled, Allowed)]
+#[default_command(greet_show)]
+#[commands(greet_show, greet_set, greet_clear, greet_off, greet_on)]
+pub struct Greetings;
+
+#[command("show")]
+pub async fn greet_show(ctx: &Context, msg: &Message) -> CommandResult {
+    let preferences = get_user_preferences(ctx, msg.author.id).await;
+
+    let reply = if preferences.greetings_muted {
+        "You are not greeted when joining".to_string()
+    } else {
+        match preferences.greeting_sound {
+            Some(sound) => format!("You are greeted with `{}`", sound),
+            None => "You are greeted with the server's welcome sound".to_string(),
+        }
+    };
+    check_msg(msg.reply(ctx, reply).await);
+
+    Ok(())
+}
+
+#[command("set")]
+pub async fn greet_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let sound = args
+        .single_quoted::<String>()
+        .ok()
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .greet set <sound>",
+        })?;
+    get_sound_path(ctx, msg.guild_id, &sound).await?;
+
+    update_preferences(
+        ctx,
+        msg,
+        format!("You will be greeted with `{}`", sound),
+        |preferences| {
+            preferences.greeting_sound = Some(sound.clone());
+            preferences.greetings_muted = false;
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("clear")]
+pub async fn greet_clear(ctx: &Context, msg: &Message) -> CommandResult {
+    update_preferences(
+        ctx,
+        msg,
+        "You will be greeted with the server's welcome sound".to_string(),
+        |preferences| preferences.greeting_sound = None,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("off")]
+pub async fn greet_off(ctx: &Context, msg: &Message) -> CommandResult {
+    update_preferences(
+        ctx,
+        msg,
+        "You won't be greeted anymore".to_string(),
+        |preferences| preferences.greetings_muted = true,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command("on")]
+pub async fn greet_on(ctx: &Context, msg: &Message) -> CommandResult {
+    update_preferences(
+        ctx,
+        msg,
+        "You will be greeted again".to_string(),
+        |preferences| preferences.greetings_muted = false,
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn update_preferences(
+    ctx: &Context,
+    msg: &Message,
+    reply: String,
+    change: impl FnOnce(&mut UserPreferences),
+) -> Result<(), CommandError> {
+    let mut preferences = get_user_preferences(ctx, msg.author.id).await;
+    change(&mut preferences);
+
+    get_storage(ctx)
+        .await
+        .save_user_preferences(msg.author.id, &preferences)
+        .context(command_error::StorageSnafu {
+            action: "save your greeting",
+        })?;
+    check_msg(msg.reply(ctx, reply).await);
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::Message,
+    prelude::{Context, Mentionable},
+};
+use snafu::prelude::*;
+
+use crate::{
+    commands::{permissions::ALLOWED_CHECK, settings::GROUPENABLED_CHECK},
+    models::{
+        command_error::command_error,
+        sound_library::{get_sound_library, SoundEntry},
+    },
+    storage::get_storage,
+    utils::{checks::check_msg, format::format_duration, pagination::paginate},
+};
+
+const HISTORY_LENGTH: usize = 10;
+
+#[group]
+#[prefixes("sounds")]
+#[checks(GroupEnabled, Allowed)]
+#[default_command(list)]
+#[commands(list, search, history)]
+pub struct Library;
+
+#[command]
+pub async fn list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let page = args.single::<usize>().unwrap_or(1);
+
+    let library_lock = get_sound_library(ctx).await;
+    let library = library_lock.read().await;
+    let entries = library.list(msg.guild_id);
+
+    send_sound_page(ctx, msg, "Sounds", &entries, page).await;
+
+    Ok(())
+}
+
+#[command]
+pub async fn search(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let term = args
+        .single_quoted::<String>()
+        .ok()
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .sounds search <term> [page]",
+        })?;
+    let page = args.single::<usize>().unwrap_or(1);
+
+    let library_lock = get_sound_library(ctx).await;
+    let library = library_lock.read().await;
+    let entries = library.search(msg.guild_id, &term);
+
+    if entries.is_empty() {
+        check_msg(
+            msg.channel_id
+                .say(ctx, format!("No sounds matching `{}`", term))
+                .await,
+        );
+        return Ok(());
+    }
+
+    send_sound_page(
+        ctx,
+        msg,
+        &format!("Sounds matching `{}`", term),
+        &entries,
+        page,
+    )
+    .await;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn history(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let records = get_storage(ctx)
+        .await
+        .play_history(guild_id, HISTORY_LENGTH)
+        .context(command_error::StorageSnafu {
+            action: "load the play history",
+        })?;
+
+    if records.is_empty() {
+        check_msg(msg.channel_id.say(ctx, "Nothing played yet").await);
+        return Ok(());
+    }
+
+    let description = records
+        .iter()
+        .map(|record| {
+            format!(
+                "`{}` by {} <t:{}:R>",
+                record.track,
+                record.user_id.mention(),
+                record.played_at
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    check_msg(
+        msg.channel_id
+            .send_message(ctx, |m| {
+                m.embed(|e| e.title("Recently played").description(description))
+            })
+            .await,
+    );
+
+    Ok(())
+}
+
+async fn send_sound_page(
+    ctx: &Context,
+    msg: &Message,
+    title: &str,
+    entries: &[&SoundEntry],
+    page: usize,
+) {
+    let page = paginate(entries, page);
+    let description = page
+        .items
+        .iter()
+        .map(|entry| {
+            format!(
+                "`{}` ({}) {}",
+                entry.file_name(),
+                format_duration(entry.duration),
+                entry.tags.join(", ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    check_msg(
+        msg.channel_id
+            .send_message(ctx, |m| {
+                m.embed(|e| {
+                    e.title(title).description(description).footer(|f| {
+                        f.text(format!(
+                            "Page {}/{} - {} sounds",
+                            page.number,
+                            page.total,
+                            entries.len()
+                        ))
+                    })
+                })
+            })
+            .await,
+    );
+}
@@ -1,4 +1,11 @@
 pub mod essentials;
 pub mod funny;
+pub mod greetings;
 pub mod help;
+pub mod library;
+pub mod permissions;
+pub mod queue;
+pub mod settings;
+pub mod slash;
+pub mod sound_management;
 pub mod sounds;
@@ -0,0 +1,236 @@
+use serenity::{
+    framework::standard::{
+        macros::{check, command, group},
+        Args, CommandOptions, CommandResult, Reason,
+    },
+    model::prelude::{GuildId, Message, UserId},
+    prelude::Context,
+    utils::{parse_role, parse_username},
+};
+use snafu::prelude::*;
+
+use crate::{
+    commands::settings::{find_command, TOGGLEABLE_GROUPS},
+    models::{
+        command_error::{command_error, CommandError},
+        permission_rule::{is_allowed, PermissionRule, RuleSubject},
+    },
+    storage::{get_guild_settings, get_storage},
+    utils::checks::check_msg,
+};
+
+#[group]
+#[prefixes("perm")]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+#[default_command(perm_list)]
+#[commands(perm_list, perm_allow, perm_deny, perm_remove)]
+pub struct Permissions;
+
+// Applies the guild's `.perm` rules. Administrators are never held back, so they can't lock
+// themselves out.
+#[check]
+#[name = "Allowed"]
+async fn allowed_check(
+    ctx: &Context,
+    msg: &Message,
+    _: &mut Args,
+    options: &CommandOptions,
+) -> Result<(), Reason> {
+    check_allowed(ctx, msg.guild_id, msg.author.id, options).await
+}
+
+/// The `Allowed` check, also run before slash commands.
+pub(crate) async fn check_allowed(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+    options: &CommandOptions,
+) -> Result<(), Reason> {
+    let guild_id = match guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let (group, command) = match find_command(options) {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+
+    let rules = get_guild_settings(ctx, guild_id).await.permission_rules;
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let member = guild_id
+        .member(ctx, user_id)
+        .await
+        .map_err(|why| Reason::Log(format!("Could not fetch member: {:?}", why)))?;
+    let is_admin = member
+        .permissions(ctx)
+        .map(|permissions| permissions.administrator())
+        .unwrap_or(false);
+    let roles: Vec<u64> = member.roles.iter().map(|role_id| role_id.0).collect();
+
+    if is_admin || is_allowed(&rules, user_id.0, &roles, group.name, command.options.names) {
+        Ok(())
+    } else {
+        Err(Reason::User(format!(
+            "You are not allowed to use `{}` in this server",
+            command.options.names[0]
+        )))
+    }
+}
+
+#[command("list")]
+pub async fn perm_list(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+    let rules = get_guild_settings(ctx, guild_id).await.permission_rules;
+
+    let reply = if rules.is_empty() {
+        "No rules, everyone can use every command".to_string()
+    } else {
+        rules
+            .iter()
+            .map(|rule| {
+                let verb = if rule.allow { "allow" } else { "deny" };
+                format!("{} {} `{}`", verb, rule.subject, rule.target)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    check_msg(msg.channel_id.say(ctx, reply).await);
+
+    Ok(())
+}
+
+#[command("allow")]
+pub async fn perm_allow(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    set_rule(ctx, msg, args, true).await?;
+
+    Ok(())
+}
+
+#[command("deny")]
+pub async fn perm_deny(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    set_rule(ctx, msg, args, false).await?;
+
+    Ok(())
+}
+
+#[command("remove")]
+pub async fn perm_remove(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+
+    let (subject, target) = parse_rule_args(args)?;
+
+    let mut settings = get_guild_settings(ctx, guild_id).await;
+    let removed = settings.remove_rule(subject, &target);
+    ensure!(
+        removed,
+        command_error::BadArgumentSnafu {
+            reason: format!("There is no rule for {} on `{}`", subject, target),
+        }
+    );
+
+    get_storage(ctx)
+        .await
+        .save_guild_settings(guild_id, &settings)
+        .context(command_error::StorageSnafu {
+            action: "save the rule",
+        })?;
+    check_msg(
+        msg.reply(
+            ctx,
+            format!("Removed the rule for {} on `{}`", subject, target),
+        )
+        .await,
+    );
+
+    Ok(())
+}
+
+async fn set_rule(
+    ctx: &Context,
+    msg: &Message,
+    args: Args,
+    allow: bool,
+) -> Result<(), CommandError> {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+
+    let (subject, target) = parse_rule_args(args)?;
+
+    let mut settings = get_guild_settings(ctx, guild_id).await;
+    settings.set_rule(PermissionRule {
+        subject,
+        target: target.clone(),
+        allow,
+    });
+
+    get_storage(ctx)
+        .await
+        .save_guild_settings(guild_id, &settings)
+        .context(command_error::StorageSnafu {
+            action: "save the rule",
+        })?;
+
+    let verb = if allow { "can" } else { "can't" };
+    check_msg(
+        msg.reply(ctx, format!("{} {} use `{}` now", subject, verb, target))
+            .await,
+    );
+
+    Ok(())
+}
+
+/// Reads `<@role|@user|everyone> <group or command>`.
+fn parse_rule_args(mut args: Args) -> Result<(RuleSubject, String), CommandError> {
+    const USAGE: &str = "Usage: .perm allow|deny|remove <@role|@user|everyone> <group or command>";
+
+    let subject = args
+        .single::<String>()
+        .ok()
+        .context(command_error::UsageSnafu { usage: USAGE })?;
+    let target = args
+        .single::<String>()
+        .ok()
+        .context(command_error::UsageSnafu { usage: USAGE })?;
+
+    let subject = if subject.eq_ignore_ascii_case("everyone") {
+        RuleSubject::Everyone
+    } else if let Some(role_id) = parse_role(&subject) {
+        RuleSubject::Role(role_id)
+    } else if let Some(user_id) = parse_username(&subject) {
+        RuleSubject::User(user_id)
+    } else {
+        return command_error::UsageSnafu { usage: USAGE }.fail();
+    };
+
+    let target = target.to_lowercase();
+    ensure!(
+        is_known_target(&target),
+        command_error::BadArgumentSnafu {
+            reason: format!(
+                "Unknown group or command `{}`. Groups: {}",
+                target,
+                TOGGLEABLE_GROUPS
+                    .iter()
+                    .map(|group| group.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    );
+
+    Ok((subject, target))
+}
+
+fn is_known_target(target: &str) -> bool {
+    TOGGLEABLE_GROUPS.iter().any(|group| {
+        group.name.eq_ignore_ascii_case(target)
+            || group
+                .options
+                .commands
+                .iter()
+                .any(|command| command.options.names.contains(&target))
+    })
+}
@@ -0,0 +1,581 @@
+use std::{collections::VecDeque, time::Duration};
+
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::{GuildId, Message},
+    prelude::{Context, Mentionable},
+};
+use snafu::prelude::*;
+use songbird::tracks::{Queued, TrackHandle};
+
+use crate::{
+    commands::{permissions::ALLOWED_CHECK, settings::GROUPENABLED_CHECK, sounds::play},
+    models::{
+        command_error::{command_error, CommandError},
+        guild_settings::parse_volume,
+        loop_mode::{get_loop_mode, get_loop_modes, LoopMode},
+        track_request::get_request,
+    },
+    storage::{get_guild_settings, get_storage},
+    utils::{
+        checks::check_msg,
+        format::{format_duration, progress_bar},
+        invocation::Invocation,
+        pagination::{paginate, PAGE_SIZE},
+        parse::parse_timestamp,
+        queue_edit::{drain_before, drain_upcoming, move_entry, remove_at, shuffle_upcoming},
+    },
+};
+
+#[group]
+#[checks(GroupEnabled, Allowed)]
+#[commands(
+    queue,
+    now_playing,
+    queue_remove,
+    queue_move,
+    shuffle,
+    clear,
+    skipto,
+    loop_mode,
+    pause,
+    resume,
+    seek,
+    forward,
+    rewind,
+    volume
+)]
+pub struct Queue;
+
+#[command("np")]
+#[only_in(guilds)]
+pub async fn now_playing(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+    let current = current_track(ctx, guild_id).await?;
+
+    let description = describe_current(&current).await;
+    let footer = format!("Loop: {}", get_loop_mode(ctx, guild_id).await);
+    check_msg(
+        msg.channel_id
+            .send_message(ctx, |m| {
+                m.embed(|e| {
+                    e.title("Now playing")
+                        .description(description)
+                        .footer(|f| f.text(footer))
+                })
+            })
+            .await,
+    );
+
+    Ok(())
+}
+
+/// Same as `play`. Kept as its own command so `.queue list` doesn't swallow `.play list`.
+#[command]
+#[only_in(guilds)]
+#[sub_commands(queue_list)]
+pub async fn queue(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    play(ctx, msg, args).await
+}
+
+#[command("list")]
+#[only_in(guilds)]
+pub async fn queue_list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let page = args.single::<usize>().unwrap_or(1);
+    run_queue_list(ctx, &invocation, page).await?;
+
+    Ok(())
+}
+
+#[command("remove")]
+#[only_in(guilds)]
+pub async fn queue_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let position = args
+        .single::<usize>()
+        .ok()
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .remove <position>",
+        })?;
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    run_queue_remove(ctx, &invocation, position).await?;
+
+    Ok(())
+}
+
+pub(crate) async fn run_queue_list(
+    ctx: &Context,
+    invocation: &Invocation,
+    page: usize,
+) -> Result<(), CommandError> {
+    send_queue_page(ctx, invocation, "Queue", page).await
+}
+
+pub(crate) async fn run_queue_remove(
+    ctx: &Context,
+    invocation: &Invocation,
+    position: usize,
+) -> Result<(), CommandError> {
+    let removed = edit_queue(ctx, invocation.guild_id, |queue| remove_at(queue, position)).await?;
+    let _ = removed.stop();
+    let title = format!("Removed {}", track_title(&removed).await);
+    send_queue_page(ctx, invocation, &title, 1).await
+}
+
+#[command("move")]
+#[only_in(guilds)]
+pub async fn queue_move(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let from = args.single::<usize>().ok();
+    let to = args.single::<usize>().ok();
+    let (from, to) = from.zip(to).context(command_error::UsageSnafu {
+        usage: "Usage: .move <from> <to>",
+    })?;
+
+    edit_queue(ctx, invocation.guild_id, |queue| {
+        move_entry(queue, from, to)
+    })
+    .await?;
+    let title = format!("Moved {} to position {}", from, to);
+    send_queue_page(ctx, &invocation, &title, 1).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn shuffle(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    edit_queue(ctx, invocation.guild_id, shuffle_upcoming).await?;
+    send_queue_page(ctx, &invocation, "Shuffled the queue", 1).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn clear(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let removed = edit_queue(ctx, invocation.guild_id, |queue| Ok(drain_upcoming(queue))).await?;
+    for track in &removed {
+        let _ = track.stop();
+    }
+    let title = format!("Cleared {} tracks", removed.len());
+    send_queue_page(ctx, &invocation, &title, 1).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn skipto(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let position = args
+        .single::<usize>()
+        .ok()
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .skipto <position>",
+        })?;
+
+    // The queue only moves on by itself when the track at its front ends, so after taking the
+    // current track out the new front has to be started here.
+    let skipped = edit_queue(ctx, invocation.guild_id, |queue| {
+        let mut skipped = drain_before(queue, position)?;
+        skipped.extend(queue.pop_front());
+        if let Some(next) = queue.front() {
+            let _ = next.play();
+        }
+        Ok(skipped)
+    })
+    .await?;
+
+    for track in &skipped {
+        let _ = track.stop();
+    }
+    let title = format!("Skipped {} tracks", skipped.len());
+    send_queue_page(ctx, &invocation, &title, 1).await?;
+
+    Ok(())
+}
+
+#[command("loop")]
+#[only_in(guilds)]
+pub async fn loop_mode(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+
+    let mode = match args.single::<String>() {
+        Ok(mode) => mode,
+        Err(_) => {
+            let mode = get_loop_mode(ctx, guild_id).await;
+            check_msg(
+                msg.reply(
+                    ctx,
+                    format!("Loop is {}. Usage: .loop <track|queue|off>", mode),
+                )
+                .await,
+            );
+            return Ok(());
+        }
+    };
+    let mode = mode
+        .parse::<LoopMode>()
+        .map_err(|reason| CommandError::BadArgument { reason })?;
+
+    get_loop_modes(ctx)
+        .await
+        .write()
+        .await
+        .insert(guild_id, mode);
+
+    if let Some(current) = current_queue(ctx, guild_id).await.first() {
+        let _ = match mode {
+            LoopMode::Track => current.enable_loop(),
+            LoopMode::Queue | LoopMode::Off => current.disable_loop(),
+        };
+    }
+
+    check_msg(msg.reply(ctx, format!("Loop is now {}", mode)).await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn pause(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+    let current = current_track(ctx, guild_id).await?;
+
+    current
+        .pause()
+        .context(command_error::TrackSnafu { action: "pause" })?;
+    check_msg(msg.reply(ctx, "Paused").await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn resume(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+    let current = current_track(ctx, guild_id).await?;
+
+    current
+        .play()
+        .context(command_error::TrackSnafu { action: "resume" })?;
+    check_msg(msg.reply(ctx, "Resumed").await);
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn seek(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+    let position = args
+        .single::<String>()
+        .ok()
+        .and_then(|arg| parse_timestamp(&arg))
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .seek <mm:ss>",
+        })?;
+
+    let current = current_track(ctx, guild_id).await?;
+    seek_to(ctx, msg, &current, position).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn forward(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+    let seconds =
+        args.single::<u64>()
+            .map(Duration::from_secs)
+            .ok()
+            .context(command_error::UsageSnafu {
+                usage: "Usage: .forward <seconds>",
+            })?;
+
+    let current = current_track(ctx, guild_id).await?;
+    let position = current.get_info().await.map(|state| state.position);
+    seek_to(ctx, msg, &current, position.unwrap_or_default() + seconds).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn rewind(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+    let seconds =
+        args.single::<u64>()
+            .map(Duration::from_secs)
+            .ok()
+            .context(command_error::UsageSnafu {
+                usage: "Usage: .rewind <seconds>",
+            })?;
+
+    let current = current_track(ctx, guild_id).await?;
+    let position = current.get_info().await.map(|state| state.position);
+    let position = position.unwrap_or_default().saturating_sub(seconds);
+    seek_to(ctx, msg, &current, position).await?;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+pub async fn volume(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+    let mut settings = get_guild_settings(ctx, guild_id).await;
+
+    let volume = match args.single::<String>() {
+        Ok(volume) => volume,
+        Err(_) => {
+            check_msg(
+                msg.reply(ctx, format!("Volume is {}%", settings.music_volume))
+                    .await,
+            );
+            return Ok(());
+        }
+    };
+    settings.music_volume =
+        parse_volume(&volume).map_err(|reason| CommandError::BadArgument { reason })?;
+
+    for track in current_queue(ctx, guild_id).await {
+        let _ = track.set_volume(settings.music_volume as f32 / 100.0);
+    }
+
+    get_storage(ctx)
+        .await
+        .save_guild_settings(guild_id, &settings)
+        .context(command_error::StorageSnafu {
+            action: "save the volume, it will reset next time",
+        })?;
+    check_msg(
+        msg.reply(ctx, format!("Volume set to {}%", settings.music_volume))
+            .await,
+    );
+
+    Ok(())
+}
+
+/// Replies with one page of the queue: the track that is playing and the ones after it.
+async fn send_queue_page(
+    ctx: &Context,
+    invocation: &Invocation,
+    title: &str,
+    page: usize,
+) -> Result<(), CommandError> {
+    let guild_id = invocation.guild_id;
+    let tracks = current_queue(ctx, guild_id).await;
+    let current = match tracks.first() {
+        Some(current) => current,
+        None => {
+            check_msg(invocation.channel_id.say(ctx, "The queue is empty").await);
+            return Ok(());
+        }
+    };
+
+    let upcoming = &tracks[1..];
+    let page = paginate(upcoming, page);
+    let first_position = (page.number - 1) * PAGE_SIZE + 1;
+
+    let mut lines = Vec::new();
+    for (i, track) in page.items.iter().enumerate() {
+        lines.push(format!(
+            "`{}.` {}",
+            first_position + i,
+            describe_track(track).await
+        ));
+    }
+    if lines.is_empty() {
+        lines.push("Nothing else queued".to_string());
+    }
+
+    let now_playing = describe_current(current).await;
+    let footer = format!(
+        "Page {}/{} - {} upcoming - Loop: {}",
+        page.number,
+        page.total,
+        upcoming.len(),
+        get_loop_mode(ctx, guild_id).await
+    );
+    check_msg(
+        invocation
+            .channel_id
+            .send_message(ctx, |m| {
+                m.embed(|e| {
+                    e.title(title)
+                        .field("Now playing", now_playing, false)
+                        .field("Up next", lines.join("\n"), false)
+                        .footer(|f| f.text(footer))
+                })
+            })
+            .await,
+    );
+
+    Ok(())
+}
+
+/// Runs `edit` on the guild's queue while holding it. Fails when the bot is not in a channel.
+async fn edit_queue<T>(
+    ctx: &Context,
+    guild_id: GuildId,
+    edit: impl FnOnce(&mut VecDeque<Queued>) -> Result<T, String>,
+) -> Result<T, CommandError> {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let handler_lock = manager
+        .get(guild_id)
+        .context(command_error::NotInVoiceSnafu)?;
+    let result = handler_lock.lock().await.queue().modify_queue(edit);
+
+    result.map_err(|reason| CommandError::BadArgument { reason })
+}
+
+/// How long until the tracks in `ahead` are done playing, with a `+` when some of them have no
+/// known duration.
+pub async fn estimate_wait(ahead: &[TrackHandle]) -> String {
+    let mut wait = Duration::ZERO;
+    let mut complete = true;
+
+    for (i, track) in ahead.iter().enumerate() {
+        let duration = match track.metadata().duration {
+            Some(duration) => duration,
+            None => {
+                complete = false;
+                continue;
+            }
+        };
+        let played = match (i, track.get_info().await) {
+            (0, Ok(state)) => state.position,
+            _ => Duration::ZERO,
+        };
+        wait += duration.saturating_sub(played);
+    }
+
+    let wait = format_duration(Some(wait));
+    if complete {
+        wait
+    } else {
+        format!("{}+", wait)
+    }
+}
+
+async fn seek_to(
+    ctx: &Context,
+    msg: &Message,
+    track: &TrackHandle,
+    position: Duration,
+) -> Result<(), CommandError> {
+    ensure!(
+        track.is_seekable(),
+        command_error::BadArgumentSnafu {
+            reason: "This track can't be seeked",
+        }
+    );
+    if let Some(duration) = track.metadata().duration {
+        ensure!(
+            position < duration,
+            command_error::BadArgumentSnafu {
+                reason: format!("The track is only {} long", format_duration(Some(duration))),
+            }
+        );
+    }
+
+    track
+        .seek_time(position)
+        .context(command_error::TrackSnafu { action: "seek" })?;
+    check_msg(
+        msg.reply(ctx, format!("Moved to {}", format_duration(Some(position))))
+            .await,
+    );
+
+    Ok(())
+}
+
+/// The track that is playing.
+async fn current_track(ctx: &Context, guild_id: GuildId) -> Result<TrackHandle, CommandError> {
+    current_queue(ctx, guild_id)
+        .await
+        .into_iter()
+        .next()
+        .context(command_error::NothingPlayingSnafu)
+}
+
+/// The guild's queue, with the track that is playing first.
+async fn current_queue(ctx: &Context, guild_id: GuildId) -> Vec<TrackHandle> {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().current_queue(),
+        None => Vec::new(),
+    }
+}
+
+/// Position and title of each upcoming track, as `.remove` and `.skipto` number them.
+pub(crate) async fn upcoming_titles(ctx: &Context, guild_id: GuildId) -> Vec<(usize, String)> {
+    let tracks = current_queue(ctx, guild_id).await;
+
+    let mut titles = Vec::new();
+    for (i, track) in tracks.iter().enumerate().skip(1) {
+        titles.push((i, track_title(track).await));
+    }
+
+    titles
+}
+
+async fn track_title(track: &TrackHandle) -> String {
+    match get_request(track).await {
+        Some(request) => request.title,
+        None => track
+            .metadata()
+            .title
+            .clone()
+            .unwrap_or_else(|| "Unknown track".to_string()),
+    }
+}
+
+/// One line per track: linked title, duration and who asked for it.
+async fn describe_track(track: &TrackHandle) -> String {
+    let duration = format_duration(track.metadata().duration);
+
+    match get_request(track).await {
+        Some(request) => format!(
+            "[{}]({}) ({}) - {}",
+            request.title,
+            request.url,
+            duration,
+            request.requester.mention()
+        ),
+        None => format!("{} ({})", track_title(track).await, duration),
+    }
+}
+
+/// The track line followed by a progress bar with the elapsed time.
+async fn describe_current(track: &TrackHandle) -> String {
+    let total = track.metadata().duration;
+    let elapsed = match track.get_info().await {
+        Ok(state) => state.position,
+        Err(_) => Default::default(),
+    };
+
+    format!(
+        "{}\n{} `{} / {}`",
+        describe_track(track).await,
+        progress_bar(elapsed, total),
+        format_duration(Some(elapsed)),
+        format_duration(total)
+    )
+}
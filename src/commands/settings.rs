@@ -0,0 +1,218 @@
+use serenity::{
+    framework::standard::{
+        macros::{check, command, group},
+        Args, Command, CommandGroup, CommandOptions, CommandResult, Reason,
+    },
+    model::prelude::{GuildId, Message},
+    prelude::Context,
+};
+use snafu::prelude::*;
+
+use crate::{
+    commands::{
+        essentials::ESSENTIALS_GROUP, funny::FUNNY_GROUP, greetings::GREETINGS_GROUP,
+        library::LIBRARY_GROUP, queue::QUEUE_GROUP, sounds::SOUNDS_GROUP,
+    },
+    models::{
+        command_error::{command_error, CommandError},
+        guild_settings::{GuildSettings, SETTING_KEYS},
+    },
+    storage::{get_guild_settings, get_storage},
+    utils::checks::check_msg,
+};
+
+/// Groups an admin can turn off with `disabled_groups`. `Settings` is left out so a guild can't
+/// lock itself out of `.config`.
+pub static TOGGLEABLE_GROUPS: [&CommandGroup; 6] = [
+    &ESSENTIALS_GROUP,
+    &FUNNY_GROUP,
+    &SOUNDS_GROUP,
+    &QUEUE_GROUP,
+    &LIBRARY_GROUP,
+    &GREETINGS_GROUP,
+];
+
+#[group]
+#[prefixes("config")]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+#[default_command(config_get)]
+#[commands(config_get, config_set, config_reset)]
+pub struct Settings;
+
+#[check]
+#[name = "GroupEnabled"]
+async fn group_enabled_check(
+    ctx: &Context,
+    msg: &Message,
+    _: &mut Args,
+    options: &CommandOptions,
+) -> Result<(), Reason> {
+    check_group_enabled(ctx, msg.guild_id, options).await
+}
+
+/// The `GroupEnabled` check, also run before slash commands.
+pub(crate) async fn check_group_enabled(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    options: &CommandOptions,
+) -> Result<(), Reason> {
+    let guild_id = match guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let group = match find_command(options) {
+        Some((group, _)) => group,
+        None => return Ok(()),
+    };
+
+    if get_guild_settings(ctx, guild_id)
+        .await
+        .is_group_enabled(group.name)
+    {
+        Ok(())
+    } else {
+        Err(Reason::User(format!(
+            "The {} commands are disabled in this server",
+            group.name
+        )))
+    }
+}
+
+#[command("get")]
+pub async fn config_get(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+    let settings = get_guild_settings(ctx, guild_id).await;
+
+    let keys = match args.single::<String>() {
+        Ok(key) => vec![key],
+        Err(_) => SETTING_KEYS.iter().map(|key| key.to_string()).collect(),
+    };
+
+    let mut lines = Vec::new();
+    for key in keys {
+        let value = settings
+            .get(&key)
+            .map_err(|reason| CommandError::BadArgument { reason })?;
+        lines.push(format!("`{}`: {}", key, value));
+    }
+
+    check_msg(msg.channel_id.say(ctx, lines.join("\n")).await);
+
+    Ok(())
+}
+
+#[command("set")]
+pub async fn config_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+
+    let key = args
+        .single::<String>()
+        .ok()
+        .filter(|_| !args.is_empty())
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .config set <setting> <value>",
+        })?;
+    let value = args.rest().trim().to_string();
+
+    if key == "disabled_groups" {
+        check_group_names(&value).map_err(|reason| CommandError::BadArgument { reason })?;
+    }
+
+    let mut settings = get_guild_settings(ctx, guild_id).await;
+    settings
+        .set(&key, &value)
+        .map_err(|reason| CommandError::BadArgument { reason })?;
+
+    save_settings(ctx, msg, &settings, &key).await?;
+
+    Ok(())
+}
+
+#[command("reset")]
+pub async fn config_reset(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+
+    let key = args
+        .single::<String>()
+        .ok()
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .config reset <setting>",
+        })?;
+
+    let mut settings = get_guild_settings(ctx, guild_id).await;
+    settings
+        .reset(&key)
+        .map_err(|reason| CommandError::BadArgument { reason })?;
+
+    save_settings(ctx, msg, &settings, &key).await?;
+
+    Ok(())
+}
+
+async fn save_settings(
+    ctx: &Context,
+    msg: &Message,
+    settings: &GuildSettings,
+    key: &str,
+) -> Result<(), CommandError> {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
+
+    get_storage(ctx)
+        .await
+        .save_guild_settings(guild_id, settings)
+        .context(command_error::StorageSnafu {
+            action: "save the settings",
+        })?;
+
+    let value = settings.get(key).unwrap_or_default();
+    check_msg(msg.reply(ctx, format!("`{}` is now {}", key, value)).await);
+
+    Ok(())
+}
+
+fn check_group_names(value: &str) -> Result<(), String> {
+    let unknown: Vec<&str> = value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && !name.eq_ignore_ascii_case("none"))
+        .filter(|name| {
+            !TOGGLEABLE_GROUPS
+                .iter()
+                .any(|group| group.name.eq_ignore_ascii_case(name))
+        })
+        .collect();
+
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        let names: Vec<&str> = TOGGLEABLE_GROUPS.iter().map(|group| group.name).collect();
+        Err(format!(
+            "Unknown groups: {}. Groups that can be disabled: {}",
+            unknown.join(", "),
+            names.join(", ")
+        ))
+    }
+}
+
+/// The toggleable group and top-level command the options belong to. Sub-commands resolve to
+/// the command they are under.
+pub(crate) fn find_command(
+    options: &CommandOptions,
+) -> Option<(&'static CommandGroup, &'static Command)> {
+    TOGGLEABLE_GROUPS.iter().find_map(|group| {
+        group
+            .options
+            .commands
+            .iter()
+            .find(|command| contains_command(&[*command], options))
+            .map(|command| (*group, *command))
+    })
+}
+
+fn contains_command(commands: &[&Command], options: &CommandOptions) -> bool {
+    commands.iter().any(|command| {
+        std::ptr::eq(command.options, options)
+            || contains_command(command.options.sub_commands, options)
+    })
+}
@@ -0,0 +1,529 @@
+use std::convert::TryFrom;
+
+use serenity::{
+    builder::{CreateApplicationCommandOption, CreateApplicationCommands},
+    framework::standard::{Command, Reason},
+    json::Value,
+    model::{
+        application::{
+            command::{Command as ApplicationCommand, CommandOptionType},
+            interaction::{
+                application_command::{
+                    ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+                },
+                autocomplete::AutocompleteInteraction,
+                InteractionResponseType,
+            },
+        },
+        channel::Attachment,
+        id::GuildId,
+    },
+    prelude::Context,
+};
+use tracing::{error, info};
+use tracing_futures::Instrument;
+
+use crate::{
+    command_core::{
+        playback, report_failure, serenity_responder::InteractionResponder,
+        songbird_backend::SongbirdBackend,
+    },
+    commands::{
+        essentials::{run_ping, ESSENTIALS_GROUP},
+        funny::{run_ba, FUNNY_GROUP},
+        permissions::check_allowed,
+        queue::{run_queue_list, run_queue_remove, upcoming_titles, QUEUE_GROUP},
+        settings::check_group_enabled,
+        sound_management::{
+            check_sound_manager, run_add_sound, run_remove_sound, run_rename_sound,
+        },
+        sounds::{run_play, run_siren, run_skip, run_sound, run_spam, run_stop, SOUNDS_GROUP},
+    },
+    models::{
+        command_error::{command_error, CommandError},
+        config::get_config,
+        sound_library::get_sound_library,
+    },
+    utils::{
+        checks::check_result, error_report::ErrorReport, format::truncate, invocation::Invocation,
+        logging::command_span,
+    },
+};
+
+/// Discord shows at most this many autocomplete choices.
+const MAX_CHOICES: usize = 25;
+
+/// Registers the slash commands in the configured guild, where they show up right away, or
+/// globally, where Discord can take up to an hour to roll them out.
+pub async fn register(ctx: &Context) {
+    let result = match get_config(ctx).await.slash_guild_id {
+        Some(guild_id) => {
+            GuildId(guild_id)
+                .set_application_commands(&ctx.http, create_commands)
+                .await
+        }
+        None => {
+            ApplicationCommand::set_global_application_commands(&ctx.http, create_commands).await
+        }
+    };
+
+    match result {
+        Ok(commands) => info!("Registered {} slash commands", commands.len()),
+        Err(why) => error!("Err registering slash commands: {:?}", why),
+    }
+}
+
+fn create_commands(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
+    commands
+        .create_application_command(|c| c.name("join").description("Join your voice channel"))
+        .create_application_command(|c| c.name("leave").description("Leave the voice channel"))
+        .create_application_command(|c| c.name("ping").description("Check that the bot is up"))
+        .create_application_command(|c| {
+            c.name("ba")
+                .description("Hop in and out of your voice channel")
+                .create_option(count_option)
+        })
+        .create_application_command(|c| {
+            c.name("sound")
+                .description("Play or manage the sounds of the library")
+                .create_option(|o| {
+                    subcommand(o, "play", "Play a sound from the library")
+                        .create_sub_option(sound_option)
+                })
+                .create_option(|o| {
+                    subcommand(o, "add", "Add an audio file as a sound of this server")
+                        .create_sub_option(|o| new_name_option(o, "name"))
+                        .create_sub_option(|o| {
+                            o.name("file")
+                                .description("Audio file")
+                                .kind(CommandOptionType::Attachment)
+                                .required(true)
+                        })
+                })
+                .create_option(|o| {
+                    subcommand(o, "remove", "Delete one of this server's sounds")
+                        .create_sub_option(sound_option)
+                })
+                .create_option(|o| {
+                    subcommand(o, "rename", "Rename one of this server's sounds")
+                        .create_sub_option(sound_option)
+                        .create_sub_option(|o| new_name_option(o, "new_name"))
+                })
+        })
+        .create_application_command(|c| {
+            c.name("spam")
+                .description("Play a sound over and over")
+                .create_option(sound_option)
+                .create_option(count_option)
+        })
+        .create_application_command(|c| {
+            c.name("siren")
+                .description("Play a siren")
+                .create_option(|o| {
+                    o.name("kind")
+                        .description("Which siren")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                        .add_string_choice("tense", "tense")
+                        .add_string_choice("taci", "taci")
+                })
+                .create_option(count_option)
+        })
+        .create_application_command(|c| {
+            c.name("play")
+                .description("Queue a URL, a playlist or the result of a search")
+                .create_option(query_option)
+        })
+        .create_application_command(|c| {
+            c.name("queue")
+                .description("Add to, show or edit the queue")
+                .create_option(|o| {
+                    subcommand(o, "add", "Same as /play").create_sub_option(query_option)
+                })
+                .create_option(|o| {
+                    subcommand(o, "list", "Show the queue").create_sub_option(|o| {
+                        o.name("page")
+                            .description("Page of the queue")
+                            .kind(CommandOptionType::Integer)
+                            .min_int_value(1)
+                    })
+                })
+                .create_option(|o| {
+                    subcommand(o, "remove", "Take a track out of the queue").create_sub_option(
+                        |o| {
+                            o.name("position")
+                                .description("Track to remove")
+                                .kind(CommandOptionType::Integer)
+                                .required(true)
+                                .min_int_value(1)
+                                .set_autocomplete(true)
+                        },
+                    )
+                })
+        })
+        .create_application_command(|c| {
+            c.name("skip")
+                .description("Skip the track, or vote to skip someone else's")
+        })
+        .create_application_command(|c| c.name("stop").description("Stop and clear the queue"))
+}
+
+fn subcommand<'a>(
+    option: &'a mut CreateApplicationCommandOption,
+    name: &str,
+    description: &str,
+) -> &'a mut CreateApplicationCommandOption {
+    option
+        .name(name)
+        .description(description)
+        .kind(CommandOptionType::SubCommand)
+}
+
+fn sound_option(
+    option: &mut CreateApplicationCommandOption,
+) -> &mut CreateApplicationCommandOption {
+    option
+        .name("name")
+        .description("Sound from the library")
+        .kind(CommandOptionType::String)
+        .required(true)
+        .set_autocomplete(true)
+}
+
+fn new_name_option<'a>(
+    option: &'a mut CreateApplicationCommandOption,
+    name: &str,
+) -> &'a mut CreateApplicationCommandOption {
+    option
+        .name(name)
+        .description("Letters, digits, `_` and `-`")
+        .kind(CommandOptionType::String)
+        .required(true)
+}
+
+fn count_option(
+    option: &mut CreateApplicationCommandOption,
+) -> &mut CreateApplicationCommandOption {
+    option
+        .name("count")
+        .description("How many times")
+        .kind(CommandOptionType::Integer)
+        .min_int_value(1)
+        .max_int_value(100)
+}
+
+fn query_option(
+    option: &mut CreateApplicationCommandOption,
+) -> &mut CreateApplicationCommandOption {
+    option
+        .name("query")
+        .description("URL or search terms")
+        .kind(CommandOptionType::String)
+        .required(true)
+}
+
+/// Runs a slash command through the same code and checks as its prefix version.
+pub async fn run(ctx: &Context, command: ApplicationCommandInteraction) {
+    let span = command_span(
+        &command_name(&command),
+        command.guild_id,
+        command.channel_id,
+        command.user.id,
+    );
+
+    dispatch(ctx, command).instrument(span).await
+}
+
+async fn dispatch(ctx: &Context, command: ApplicationCommandInteraction) {
+    let name = command.data.name.as_str();
+    let subcommand = subcommand_name(&command);
+    let invocation = match Invocation::from_interaction(&command) {
+        Some(invocation) => invocation,
+        None => {
+            answer_now(ctx, &command, &CommandError::NotInGuild).await;
+            return;
+        }
+    };
+
+    if let Err(reason) = check(ctx, &invocation, name, subcommand).await {
+        let reason = match reason {
+            Reason::User(reason) | Reason::UserAndLog { user: reason, .. } => reason,
+            reason => {
+                info!("Slash command {} refused: {:?}", name, reason);
+                "You are not allowed to do that".to_string()
+            }
+        };
+        answer_now(ctx, &command, &CommandError::PermissionDenied { reason }).await;
+        return;
+    }
+
+    // Searches and playlists take longer than the three seconds Discord waits for an answer.
+    check_result(
+        command
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+            })
+            .await,
+        "Error deferring slash command",
+    );
+
+    let responder = InteractionResponder::new(ctx, &command);
+    let voice = SongbirdBackend::new(ctx);
+    let result = match (name, subcommand) {
+        ("join", _) => playback::join(&voice, &responder, &invocation).await,
+        ("leave", _) => playback::leave(&voice, &responder, &invocation).await,
+        ("ping", _) => {
+            run_ping(&responder).await;
+            Ok(())
+        }
+        ("ba", _) => run_ba(ctx, &invocation, int_option(&command, "count")).await,
+        ("sound", Some("add")) => {
+            let name = string_option(&command, "name").unwrap_or_default();
+            match attachment_option(&command, "file") {
+                Some(file) => run_add_sound(ctx, &invocation, &responder, &name, file).await,
+                None => command_error::UsageSnafu {
+                    usage: "Attach the audio file",
+                }
+                .fail(),
+            }
+        }
+        ("sound", Some("remove")) => {
+            let name = string_option(&command, "name").unwrap_or_default();
+            run_remove_sound(ctx, &invocation, &responder, &name).await
+        }
+        ("sound", Some("rename")) => {
+            let name = string_option(&command, "name").unwrap_or_default();
+            let new_name = string_option(&command, "new_name").unwrap_or_default();
+            run_rename_sound(ctx, &invocation, &responder, &name, &new_name).await
+        }
+        ("sound", _) => {
+            let name = string_option(&command, "name").unwrap_or_default();
+            run_sound(ctx, &invocation, &name).await
+        }
+        ("spam", _) => {
+            let name = string_option(&command, "name").unwrap_or_default();
+            let count = int_option(&command, "count");
+            run_spam(ctx, &invocation, &name, count).await
+        }
+        ("siren", _) => {
+            let kind = string_option(&command, "kind").unwrap_or_default();
+            let count = int_option(&command, "count");
+            run_siren(ctx, &invocation, &kind, count).await
+        }
+        ("play", _) | ("queue", Some("add")) => {
+            let query = string_option(&command, "query").unwrap_or_default();
+            run_play(ctx, &invocation, &responder, query).await
+        }
+        ("queue", Some("list")) => {
+            let page = int_option(&command, "page")
+                .and_then(|page| usize::try_from(page).ok())
+                .unwrap_or(1);
+            run_queue_list(ctx, &invocation, page).await
+        }
+        ("queue", Some("remove")) => {
+            let position = int_option(&command, "position")
+                .and_then(|position| usize::try_from(position).ok())
+                .unwrap_or_default();
+            run_queue_remove(ctx, &invocation, position).await
+        }
+        ("skip", _) => run_skip(ctx, &invocation, &responder).await,
+        ("stop", _) => run_stop(ctx, &invocation, &responder).await,
+        _ => command_error::BadArgumentSnafu {
+            reason: format!("Unknown command `{}`", command_name(&command)),
+        }
+        .fail(),
+    };
+
+    if let Err(why) = result {
+        report_failure(&responder, &command_name(&command), &why).await;
+    }
+    responder.finish().await;
+}
+
+/// Suggests sound names from the library, or tracks of the queue, as they are typed.
+pub async fn autocomplete(ctx: &Context, interaction: AutocompleteInteraction) {
+    let focused = match options(&interaction.data.options)
+        .iter()
+        .find(|option| option.focused)
+    {
+        Some(option) => option,
+        None => return,
+    };
+    let typed = match &focused.value {
+        Some(Value::String(typed)) => typed.clone(),
+        Some(value) => value.to_string(),
+        None => String::new(),
+    };
+
+    let result = match focused.name.as_str() {
+        "name" => {
+            let names = sound_choices(ctx, interaction.guild_id, &typed).await;
+            interaction
+                .create_autocomplete_response(&ctx.http, |r| {
+                    for name in &names {
+                        r.add_string_choice(name, name);
+                    }
+                    r
+                })
+                .await
+        }
+        "position" => {
+            let positions = position_choices(ctx, interaction.guild_id, &typed).await;
+            interaction
+                .create_autocomplete_response(&ctx.http, |r| {
+                    for (position, label) in &positions {
+                        r.add_int_choice(label, *position as i64);
+                    }
+                    r
+                })
+                .await
+        }
+        _ => return,
+    };
+
+    check_result(result, "Error sending autocomplete choices");
+}
+
+async fn sound_choices(ctx: &Context, guild_id: Option<GuildId>, typed: &str) -> Vec<String> {
+    let library_lock = get_sound_library(ctx).await;
+    let library = library_lock.read().await;
+
+    library
+        .search(guild_id, typed)
+        .into_iter()
+        .take(MAX_CHOICES)
+        .map(|entry| entry.name.clone())
+        .collect()
+}
+
+/// Upcoming tracks whose position or title contains what was typed.
+async fn position_choices(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    typed: &str,
+) -> Vec<(usize, String)> {
+    let guild_id = match guild_id {
+        Some(guild_id) => guild_id,
+        None => return Vec::new(),
+    };
+    let typed = typed.to_lowercase();
+
+    upcoming_titles(ctx, guild_id)
+        .await
+        .into_iter()
+        .filter(|(position, title)| {
+            position.to_string().starts_with(&typed) || title.to_lowercase().contains(&typed)
+        })
+        .take(MAX_CHOICES)
+        // Discord refuses choice names over 100 characters.
+        .map(|(position, title)| (position, format!("{}. {}", position, truncate(&title, 90))))
+        .collect()
+}
+
+/// Runs the checks of the prefix command with the same name.
+async fn check(
+    ctx: &Context,
+    invocation: &Invocation,
+    name: &str,
+    subcommand: Option<&str>,
+) -> Result<(), Reason> {
+    if let Some(command) = prefix_command(name) {
+        check_group_enabled(ctx, Some(invocation.guild_id), command.options).await?;
+        check_allowed(
+            ctx,
+            Some(invocation.guild_id),
+            invocation.author_id,
+            command.options,
+        )
+        .await?;
+    }
+
+    if name == "sound" && matches!(subcommand, Some("add" | "remove" | "rename")) {
+        check_sound_manager(ctx, invocation.guild_id, invocation.author_id).await?;
+    }
+
+    Ok(())
+}
+
+fn prefix_command(name: &str) -> Option<&'static Command> {
+    [&ESSENTIALS_GROUP, &FUNNY_GROUP, &SOUNDS_GROUP, &QUEUE_GROUP]
+        .iter()
+        .flat_map(|group| group.options.commands.iter())
+        .find(|command| command.options.names.contains(&name))
+        .copied()
+}
+
+/// Turns the command down before it is deferred, only showing why to the author.
+async fn answer_now(ctx: &Context, command: &ApplicationCommandInteraction, error: &CommandError) {
+    let report = ErrorReport::new(&command.data.name, error);
+
+    check_result(
+        command
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| d.embed(|e| report.embed(e)).ephemeral(true))
+            })
+            .await,
+        "Error answering slash command",
+    );
+}
+
+fn string_option(command: &ApplicationCommandInteraction, name: &str) -> Option<String> {
+    match option_value(command, name)? {
+        CommandDataOptionValue::String(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn int_option(command: &ApplicationCommandInteraction, name: &str) -> Option<i32> {
+    match option_value(command, name)? {
+        CommandDataOptionValue::Integer(value) => i32::try_from(*value).ok(),
+        _ => None,
+    }
+}
+
+fn attachment_option<'a>(
+    command: &'a ApplicationCommandInteraction,
+    name: &str,
+) -> Option<&'a Attachment> {
+    match option_value(command, name)? {
+        CommandDataOptionValue::Attachment(attachment) => Some(attachment),
+        _ => None,
+    }
+}
+
+fn option_value<'a>(
+    command: &'a ApplicationCommandInteraction,
+    name: &str,
+) -> Option<&'a CommandDataOptionValue> {
+    options(&command.data.options)
+        .iter()
+        .find(|option| option.name == name)?
+        .resolved
+        .as_ref()
+}
+
+/// The options given to the subcommand that was used, or to the command if it has none.
+fn options(options: &[CommandDataOption]) -> &[CommandDataOption] {
+    match options.first() {
+        Some(option) if option.kind == CommandOptionType::SubCommand => &option.options,
+        _ => options,
+    }
+}
+
+fn subcommand_name(command: &ApplicationCommandInteraction) -> Option<&str> {
+    command
+        .data
+        .options
+        .first()
+        .filter(|option| option.kind == CommandOptionType::SubCommand)
+        .map(|option| option.name.as_str())
+}
+
+/// The command name with its subcommand, such as `sound add`.
+fn command_name(command: &ApplicationCommandInteraction) -> String {
+    match subcommand_name(command) {
+        Some(subcommand) => format!("{} {}", command.data.name, subcommand),
+        None => command.data.name.clone(),
+    }
+}
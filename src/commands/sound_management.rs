@@ -0,0 +1,498 @@
+use std::path::Path;
+
+use serenity::{
+    framework::standard::{
+        macros::{check, command},
+        Args, CommandOptions, CommandResult, Reason,
+    },
+    model::prelude::{Attachment, GuildId, Message, RoleId, UserId},
+    prelude::Context,
+};
+use snafu::prelude::*;
+use tracing::warn;
+
+use crate::{
+    command_core::{serenity_responder::MessageResponder, Responder},
+    models::{
+        command_error::{command_error, CommandError},
+        config::{get_config, Config},
+        sound_library::{get_sound_library, is_audio_extension, SoundEntry, SoundLibrary},
+        sound_metadata::SoundMetadata,
+    },
+    storage::{get_storage, now_timestamp},
+    utils::{
+        checks::check_result,
+        invocation::Invocation,
+        probe::probe_duration,
+        sandbox::{check_file_name, resolve_in_root},
+        transcode::normalize_audio,
+    },
+};
+
+const STORED_FORMAT: &str = "mp3";
+/// Names taken by the `sound` subcommands. A sound with one of them could never be played.
+const SUBCOMMAND_NAMES: [&str; 3] = ["add", "remove", "rename"];
+const NAME_RULES: &str = "Names may only contain letters, digits, `_` and `-`, \
+                          and can't be `add`, `remove` or `rename`";
+
+#[check]
+#[name = "SoundManager"]
+async fn sound_manager_check(
+    ctx: &Context,
+    msg: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> Result<(), Reason> {
+    match msg.guild_id {
+        Some(guild_id) => check_sound_manager(ctx, guild_id, msg.author.id).await,
+        None => Err(Reason::User("Only works in a server".to_string())),
+    }
+}
+
+/// Lets administrators and members with the configured sound manager role through. Slash
+/// commands call it directly, since they can't run the prefix command's checks.
+pub(crate) async fn check_sound_manager(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<(), Reason> {
+    let member = guild_id
+        .member(ctx, user_id)
+        .await
+        .map_err(|why| Reason::Log(format!("Could not fetch member: {:?}", why)))?;
+
+    let is_admin = member
+        .permissions(ctx)
+        .map(|permissions| permissions.administrator())
+        .unwrap_or(false);
+    let has_role = match get_config(ctx).await.sound_manager_role {
+        Some(role_id) => member.roles.contains(&RoleId(role_id)),
+        None => false,
+    };
+
+    if is_admin || has_role {
+        Ok(())
+    } else {
+        Err(Reason::User(
+            "Only sound managers can change the sound library".to_string(),
+        ))
+    }
+}
+
+#[command("add")]
+#[only_in(guilds)]
+#[checks(SoundManager)]
+pub async fn add_sound(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let name = args
+        .single_quoted::<String>()
+        .ok()
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .sound add <name> with an audio file attached",
+        })?;
+    let attachment = msg.attachments.first().context(command_error::UsageSnafu {
+        usage: "Attach the audio file to the message",
+    })?;
+
+    let responder = MessageResponder::new(ctx, msg);
+    run_add_sound(ctx, &invocation, &responder, &name, attachment).await?;
+
+    Ok(())
+}
+
+/// Stores an uploaded audio file as a sound of the guild.
+pub(crate) async fn run_add_sound(
+    ctx: &Context,
+    invocation: &Invocation,
+    responder: &dyn Responder,
+    name: &str,
+    attachment: &Attachment,
+) -> Result<(), CommandError> {
+    ensure!(
+        is_valid_sound_name(name),
+        command_error::BadArgumentSnafu { reason: NAME_RULES }
+    );
+    let guild_id = Some(invocation.guild_id);
+    let config = get_config(ctx).await;
+
+    ensure!(
+        attachment.size <= config.max_upload_bytes,
+        command_error::BadArgumentSnafu {
+            reason: format!(
+                "File too big, the limit is {} KiB",
+                config.max_upload_bytes / 1024
+            ),
+        }
+    );
+
+    let extension = Path::new(&attachment.filename)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let is_audio = match &attachment.content_type {
+        Some(content_type) => content_type.starts_with("audio/"),
+        None => is_audio_extension(&extension),
+    };
+    ensure!(
+        is_audio,
+        command_error::BadArgumentSnafu {
+            reason: "That doesn't look like an audio file",
+        }
+    );
+
+    let library_lock = get_sound_library(ctx).await;
+    {
+        let mut library = library_lock.write().await;
+        if let Ok(entry) = library.resolve(guild_id, name) {
+            ensure!(
+                entry.guild_id.is_none(),
+                command_error::BadArgumentSnafu {
+                    reason: format!("`{}` already exists, remove it first", name),
+                }
+            );
+        }
+        ensure!(
+            library.reserve(guild_id, name),
+            command_error::BadArgumentSnafu {
+                reason: format!("`{}` is already being added", name),
+            }
+        );
+    }
+
+    let upload_path = std::env::temp_dir().join(format!("upload-{}.{}", attachment.id, extension));
+    let result = download_upload(attachment, &upload_path, name, guild_id, &config).await;
+    let _ = tokio::fs::remove_file(&upload_path).await;
+
+    let entry = {
+        let mut library = library_lock.write().await;
+        library.release(guild_id, name);
+        let entry = result?;
+        library.insert(entry.clone());
+        entry
+    };
+
+    responder
+        .say(format!("Added `{}`", entry.file_name()))
+        .await;
+    check_result(
+        get_storage(ctx).await.save_sound_metadata(&SoundMetadata {
+            guild_id: entry.guild_id,
+            name: entry.name.clone(),
+            uploader_id: Some(invocation.author_id),
+            added_at: now_timestamp(),
+        }),
+        "Err saving sound metadata",
+    );
+
+    Ok(())
+}
+
+#[command("remove")]
+#[only_in(guilds)]
+#[checks(SoundManager)]
+pub async fn remove_sound(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let name = args
+        .single_quoted::<String>()
+        .ok()
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .sound remove <name>",
+        })?;
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    run_remove_sound(ctx, &invocation, &MessageResponder::new(ctx, msg), &name).await?;
+
+    Ok(())
+}
+
+/// Deletes one of the guild's own sounds.
+pub(crate) async fn run_remove_sound(
+    ctx: &Context,
+    invocation: &Invocation,
+    responder: &dyn Responder,
+    name: &str,
+) -> Result<(), CommandError> {
+    let config = get_config(ctx).await;
+    let library_lock = get_sound_library(ctx).await;
+    let mut library = library_lock.write().await;
+
+    let entry = find_guild_sound(&library, Some(invocation.guild_id), name)?;
+    let path = resolve_in_root(Path::new(&config.sound_root), &entry.relative_path())
+        .context(command_error::SoundSnafu)?;
+
+    tokio::fs::remove_file(&path)
+        .await
+        .context(command_error::IoSnafu {
+            action: "delete the file",
+        })?;
+
+    library.remove(entry.guild_id, &entry.name);
+    check_result(
+        get_storage(ctx)
+            .await
+            .delete_sound_metadata(entry.guild_id, &entry.name),
+        "Err deleting sound metadata",
+    );
+    responder
+        .say(format!("Removed `{}`", entry.file_name()))
+        .await;
+
+    Ok(())
+}
+
+#[command("rename")]
+#[only_in(guilds)]
+#[checks(SoundManager)]
+pub async fn rename_sound(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let old_name = args.single_quoted::<String>().ok();
+    let new_name = args.single_quoted::<String>().ok();
+    let (old_name, new_name) = old_name.zip(new_name).context(command_error::UsageSnafu {
+        usage: "Usage: .sound rename <old name> <new name>",
+    })?;
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+
+    let responder = MessageResponder::new(ctx, msg);
+    run_rename_sound(ctx, &invocation, &responder, &old_name, &new_name).await?;
+
+    Ok(())
+}
+
+/// Renames one of the guild's own sounds, keeping its metadata.
+pub(crate) async fn run_rename_sound(
+    ctx: &Context,
+    invocation: &Invocation,
+    responder: &dyn Responder,
+    old_name: &str,
+    new_name: &str,
+) -> Result<(), CommandError> {
+    ensure!(
+        is_valid_sound_name(new_name),
+        command_error::BadArgumentSnafu { reason: NAME_RULES }
+    );
+    let guild_id = Some(invocation.guild_id);
+    let config = get_config(ctx).await;
+    let library_lock = get_sound_library(ctx).await;
+
+    let entry = {
+        let mut library = library_lock.write().await;
+        let entry = find_guild_sound(&library, guild_id, old_name)?;
+        ensure!(
+            find_guild_sound(&library, guild_id, new_name).is_err(),
+            command_error::BadArgumentSnafu {
+                reason: format!("`{}` already exists", new_name),
+            }
+        );
+        ensure!(
+            library.reserve(guild_id, new_name),
+            command_error::BadArgumentSnafu {
+                reason: format!("`{}` is already being added", new_name),
+            }
+        );
+        entry
+    };
+
+    let result = rename_file(Path::new(&config.sound_root), &entry, new_name).await;
+
+    {
+        let mut library = library_lock.write().await;
+        library.release(guild_id, new_name);
+        let renamed = result?;
+        library.remove(entry.guild_id, &entry.name);
+        library.insert(renamed);
+    }
+    move_metadata(ctx, &entry, new_name).await;
+
+    responder
+        .say(format!("Renamed `{}` to `{}`", old_name, new_name))
+        .await;
+
+    Ok(())
+}
+
+async fn move_metadata(ctx: &Context, entry: &SoundEntry, new_name: &str) {
+    let storage = get_storage(ctx).await;
+    let metadata = match storage.sound_metadata(entry.guild_id, &entry.name) {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => return,
+        Err(why) => {
+            warn!("Err loading sound metadata: {:?}", why);
+            return;
+        }
+    };
+
+    check_result(
+        storage.delete_sound_metadata(entry.guild_id, &entry.name),
+        "Err deleting sound metadata",
+    );
+    check_result(
+        storage.save_sound_metadata(&SoundMetadata {
+            name: new_name.to_string(),
+            ..metadata
+        }),
+        "Err saving sound metadata",
+    );
+}
+
+/// Looks up a sound that belongs to the guild itself. Global sounds are shared by every guild,
+/// so they can't be changed from one of them.
+fn find_guild_sound(
+    library: &SoundLibrary,
+    guild_id: Option<GuildId>,
+    name: &str,
+) -> Result<SoundEntry, CommandError> {
+    let reason = match library.resolve(guild_id, name) {
+        Ok(entry) if entry.guild_id.is_some() && entry.guild_id == guild_id => {
+            return Ok(entry.clone())
+        }
+        Ok(entry) => format!(
+            "`{}` is a global sound, it can't be changed from here",
+            entry.name
+        ),
+        Err(_) => format!("No sound named `{}`", name),
+    };
+
+    command_error::BadArgumentSnafu { reason }.fail()
+}
+
+/// Downloads an attachment to `upload_path` and stores it as the sound `name`.
+async fn download_upload(
+    attachment: &Attachment,
+    upload_path: &Path,
+    name: &str,
+    guild_id: Option<GuildId>,
+    config: &Config,
+) -> Result<SoundEntry, CommandError> {
+    let bytes = attachment
+        .download()
+        .await
+        .context(command_error::DiscordSnafu {
+            action: "download the attachment",
+        })?;
+    tokio::fs::write(upload_path, bytes)
+        .await
+        .context(command_error::IoSnafu {
+            action: "save the attachment",
+        })?;
+
+    store_upload(
+        upload_path,
+        name,
+        &config.sound_root,
+        guild_id,
+        config.max_sound_seconds,
+    )
+    .await
+}
+
+/// Renames a sound's file and indexes it under the new name. The file is moved back when the
+/// renamed file can't be indexed, so the sound isn't lost.
+async fn rename_file(
+    root: &Path,
+    entry: &SoundEntry,
+    new_name: &str,
+) -> Result<SoundEntry, CommandError> {
+    let old_path =
+        resolve_in_root(root, &entry.relative_path()).context(command_error::SoundSnafu)?;
+    let new_path = old_path.with_file_name(format!("{}.{}", new_name, entry.format));
+
+    tokio::fs::rename(&old_path, &new_path)
+        .await
+        .context(command_error::IoSnafu {
+            action: "rename the file",
+        })?;
+
+    match SoundEntry::index(new_path.clone(), entry.guild_id).await {
+        Some(renamed) => Ok(renamed),
+        None => {
+            if let Err(why) = tokio::fs::rename(&new_path, &old_path).await {
+                warn!(
+                    "Could not move {:?} back after a failed rename: {:?}",
+                    new_path, why
+                );
+            }
+            command_error::BadArgumentSnafu {
+                reason: "ffmpeg could not read the renamed file",
+            }
+            .fail()
+        }
+    }
+}
+
+/// Probes and normalizes an uploaded file into the guild's folder of the sound root.
+async fn store_upload(
+    upload_path: &Path,
+    name: &str,
+    sound_root: &str,
+    guild_id: Option<GuildId>,
+    max_sound_seconds: u64,
+) -> Result<SoundEntry, CommandError> {
+    let duration = probe_duration(upload_path)
+        .await
+        .context(command_error::BadArgumentSnafu {
+            reason: "ffmpeg could not read that file",
+        })?;
+    ensure!(
+        duration.as_secs() <= max_sound_seconds,
+        command_error::BadArgumentSnafu {
+            reason: format!(
+                "Too long, sounds can be at most {} seconds",
+                max_sound_seconds
+            ),
+        }
+    );
+
+    let folder = match guild_id {
+        Some(guild_id) => Path::new(sound_root).join(guild_id.0.to_string()),
+        None => Path::new(sound_root).to_path_buf(),
+    };
+    tokio::fs::create_dir_all(&folder)
+        .await
+        .context(command_error::IoSnafu {
+            action: "save the file",
+        })?;
+
+    let stored_path = folder.join(format!("{}.{}", name, STORED_FORMAT));
+    let result = convert_upload(upload_path, &stored_path, guild_id).await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&stored_path).await;
+    }
+
+    result
+}
+
+async fn convert_upload(
+    upload_path: &Path,
+    stored_path: &Path,
+    guild_id: Option<GuildId>,
+) -> Result<SoundEntry, CommandError> {
+    normalize_audio(upload_path, stored_path)
+        .await
+        .context(command_error::IoSnafu {
+            action: "convert the file",
+        })?;
+
+    SoundEntry::index(stored_path.to_path_buf(), guild_id)
+        .await
+        .context(command_error::BadArgumentSnafu {
+            reason: "ffmpeg could not read the converted file",
+        })
+}
+
+fn is_valid_sound_name(name: &str) -> bool {
+    check_file_name(name).is_ok()
+        && !SUBCOMMAND_NAMES.contains(&name.to_lowercase().as_str())
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subcommand_names_are_not_valid_sound_names() {
+        assert!(is_valid_sound_name("bruh-2"));
+        assert!(!is_valid_sound_name("add"));
+        assert!(!is_valid_sound_name("Rename"));
+        assert!(!is_valid_sound_name("../bruh"));
+    }
+}
@@ -1,367 +1,434 @@
-use std::{thread, time::Duration};
+use std::time::Duration;
 
-use rand::Rng;
 use serenity::{
     framework::standard::{
         macros::{command, group},
         Args, CommandResult,
     },
-    model::prelude::Message,
+    model::{
+        application::interaction::InteractionResponseType,
+        prelude::{Message, RoleId},
+    },
     prelude::Context,
 };
-use songbird::input::{self, Restartable};
+use snafu::prelude::*;
+use tracing::warn;
 
-use crate::utils::{
-    checks::check_msg,
-    parse::{get_repeat_count, get_sound_path},
+use crate::{
+    command_core::{
+        playback, serenity_responder::MessageResponder, songbird_backend::SongbirdBackend,
+        Responder, VoiceBackend,
+    },
+    commands::{
+        permissions::ALLOWED_CHECK,
+        settings::GROUPENABLED_CHECK,
+        sound_management::{ADD_SOUND_COMMAND, REMOVE_SOUND_COMMAND, RENAME_SOUND_COMMAND},
+    },
+    models::{
+        command_error::{command_error, CommandError},
+        config::get_config,
+        guild_settings::GuildSettings,
+        skip_votes::get_skip_votes,
+    },
+    storage::{get_guild_settings, record_play},
+    utils::{
+        checks::check_result,
+        format::{format_duration, truncate},
+        invocation::Invocation,
+        parse::get_sound_path,
+        ytdl::{is_playlist, playlist, search, YtdlEntry},
+    },
 };
 
+const SIREN_USAGE: &str = "Usage: .siren <tense|taci> [count]";
+const SEARCH_RESULTS: usize = 5;
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// How many playlist entries are queued between updates of the progress message.
+const PLAYLIST_PROGRESS_STEP: usize = 10;
+
 #[group]
-#[commands(play, queue, skip, stop, sound, spam, siren)]
+#[checks(GroupEnabled, Allowed)]
+#[commands(play, skip, stop, sound, spam, siren)]
 pub struct Sounds;
 
 #[command]
 #[only_in(guilds)]
+#[sub_commands(add_sound, remove_sound, rename_sound)]
 pub async fn sound(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let file = match args.single_quoted::<String>() {
-        Ok(file) => file,
-        Err(_) => {
-            check_msg(msg.channel_id.say(ctx, "Not a string???").await);
+    let file = args
+        .single_quoted::<String>()
+        .ok()
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .sound <name>",
+        })?;
 
-            return Ok(());
-        }
-    };
-    if file.starts_with('/')
-        || file.starts_with('\\')
-        || file.starts_with('.')
-        || file.starts_with('~')
-    {
-        check_msg(msg.channel_id.say(ctx, "uhhhh no").await);
-        return Ok(());
-    }
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    run_sound(ctx, &invocation, &file).await?;
 
-    let path = get_sound_path(file.as_str());
-
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
-
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
+    Ok(())
+}
 
-        let source = match input::ffmpeg(path.clone()).await {
-            Ok(source) => source,
-            Err(why) => {
-                println!("Err starting source: {:?}", why);
+pub(crate) async fn run_sound(
+    ctx: &Context,
+    invocation: &Invocation,
+    file: &str,
+) -> Result<(), CommandError> {
+    let path = get_sound_path(ctx, Some(invocation.guild_id), file).await?;
+    let settings = get_guild_settings(ctx, invocation.guild_id).await;
 
-                check_msg(msg.channel_id.say(ctx, "Error sourcing ffmpeg").await);
+    let voice = SongbirdBackend::new(ctx);
+    playback::sound(&voice, invocation, &settings, &path).await?;
+    record_play(ctx, invocation.guild_id, invocation.author_id, file).await;
 
-                return Ok(());
-            }
-        };
-
-        // This handler object will allow you to, as needed,
-        // control the audio track via events and further commands.
-        handler.play_only_source(source);
-    }
     Ok(())
 }
 
 #[command]
 #[only_in(guilds)]
 pub async fn spam(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let file = match args.single_quoted::<String>() {
-        Ok(file) => file,
-        Err(_) => {
-            check_msg(msg.channel_id.say(ctx, "Not a string???").await);
-
-            return Ok(());
-        }
-    };
-
-    if file.starts_with('/')
-        || file.starts_with('\\')
-        || file.starts_with('.')
-        || file.starts_with('~')
-    {
-        check_msg(msg.channel_id.say(ctx, "uhhhh no").await);
-
-        return Ok(());
-    }
+    let file = args
+        .single_quoted::<String>()
+        .ok()
+        .context(command_error::UsageSnafu {
+            usage: "Usage: .spam <name> [count]",
+        })?;
 
-    let path = get_sound_path(file.as_str());
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let count = args.single::<i32>().ok();
+    run_spam(ctx, &invocation, &file, count).await?;
 
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
-
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
-
-        let repeat_count = get_repeat_count(args, 10);
-
-        for _ in 0..repeat_count {
-            let source = input::ffmpeg(path.clone()).await.unwrap();
-            let mut rng = rand::thread_rng();
-            thread::sleep(Duration::from_millis(rng.gen_range(25..125)));
-            handler.play_source(source);
-        }
-    }
     Ok(())
 }
 
+pub(crate) async fn run_spam(
+    ctx: &Context,
+    invocation: &Invocation,
+    file: &str,
+    count: Option<i32>,
+) -> Result<(), CommandError> {
+    let path = get_sound_path(ctx, Some(invocation.guild_id), file).await?;
+    let settings = get_guild_settings(ctx, invocation.guild_id).await;
+
+    let voice = SongbirdBackend::new(ctx);
+    playback::spam(&voice, invocation, &settings, &path, count).await
+}
+
 #[command]
 #[only_in(guilds)]
 pub async fn siren(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let option = match args.single::<String>(){
-        Ok(option) => {option}
-        Err(_)=> {
-            check_msg(msg.reply(ctx, "Not even a string???😤😤😤").await);
-            return  Ok(())
-        }
-    };
-    for c in option.chars(){
-        if c.is_numeric(){
-            check_msg(msg.reply(ctx, "Usage: .siren <tense|taci> <repeat count>").await);
-            return Ok(())
-        }
-    }
-    if !(option.eq(&"taci".to_string()) || option.eq(&"tense".to_string()) ){
-                    check_msg(msg.reply(ctx, "Stiu doar tense si taci").await);
-                    return Ok(())
-    }
-    let pathl = get_sound_path(format!("{}l.ogg",option).as_str());
-    let pathr = get_sound_path(format!("{}r.ogg",option).as_str());
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
+    let option = args
+        .single::<String>()
+        .ok()
+        .context(command_error::UsageSnafu { usage: SIREN_USAGE })?;
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    let count = args.single::<i32>().ok();
+    run_siren(ctx, &invocation, &option, count).await?;
 
-        let repeat_count = get_repeat_count(args, 10);
-
-        // This handler object will allow you to, as needed,
-        // control the audio track via events and further commands.
-        let mut use_left_path = false;
-
-        for _ in 0..repeat_count {
-            use_left_path = !use_left_path;
-
-            let path = if use_left_path {
-                pathl.clone()
-            } else {
-                pathr.clone()
-            };
-
-            let source = match input::ffmpeg(path).await {
-                Ok(source) => source,
-                Err(why) => {
-                    println!("Err starting source: {:?}", why);
-                    check_msg(msg.channel_id.say(ctx, "Error sourcing ffmpeg").await);
-                    return Ok(());
-                }
-            };
-
-            handler.enqueue_source(source);
-        }
-    }
     Ok(())
 }
 
+/// Alternates the left and right halves of the `tense` or `taci` siren.
+pub(crate) async fn run_siren(
+    ctx: &Context,
+    invocation: &Invocation,
+    option: &str,
+    count: Option<i32>,
+) -> Result<(), CommandError> {
+    ensure!(
+        option == "tense" || option == "taci",
+        command_error::UsageSnafu { usage: SIREN_USAGE }
+    );
+
+    let guild_id = Some(invocation.guild_id);
+    let pathl = get_sound_path(ctx, guild_id, &format!("{}l.ogg", option)).await?;
+    let pathr = get_sound_path(ctx, guild_id, &format!("{}r.ogg", option)).await?;
+    let settings = get_guild_settings(ctx, invocation.guild_id).await;
+
+    let voice = SongbirdBackend::new(ctx);
+    playback::siren(&voice, invocation, &settings, (&pathl, &pathr), count).await
+}
+
 #[command]
 #[only_in(guilds)]
-pub async fn play(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let url = match args.single::<String>() {
-        Ok(url) => url,
-        Err(_) => {
-            check_msg(
-                msg.channel_id
-                    .say(ctx, "Must provide a URL to a video or audio")
-                    .await,
-            );
-
-            return Ok(());
+pub async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let query = args.rest().trim().to_string();
+    ensure!(
+        !query.is_empty(),
+        command_error::UsageSnafu {
+            usage: "Usage: .play <URL or search terms>",
         }
-    };
-
-    if !url.starts_with("http") {
-        check_msg(msg.channel_id.say(ctx, "Must provide a valid URL").await);
-
-        return Ok(());
-    }
+    );
 
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    run_play(ctx, &invocation, &MessageResponder::new(ctx, msg), query).await?;
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
-
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
-
-        let source = match input::ytdl(&url).await {
-            Ok(source) => source,
-            Err(why) => {
-                println!("Err starting source: {:?}", why);
-
-                check_msg(msg.channel_id.say(ctx, "Error sourcing ffmpeg").await);
-
-                return Ok(());
-            }
-        };
+    Ok(())
+}
 
-        handler.enqueue_source(source);
+/// Queues a URL, a playlist, or the search result the author picks.
+pub(crate) async fn run_play(
+    ctx: &Context,
+    invocation: &Invocation,
+    responder: &dyn Responder,
+    query: String,
+) -> Result<(), CommandError> {
+    let settings = get_guild_settings(ctx, invocation.guild_id).await;
+    let voice = SongbirdBackend::new(ctx);
+    // Before searching, so nobody picks a result only to hear they aren't in a channel.
+    playback::ensure_channel(&voice, invocation, &settings).await?;
+
+    let is_url = query.starts_with("http://") || query.starts_with("https://");
+    let url = if is_url {
+        query
+    } else {
+        pick_search_result(ctx, invocation, &query).await?
+    };
 
-        check_msg(msg.channel_id.say(ctx, "Playing song").await);
+    if is_playlist(&url) {
+        play_playlist(ctx, &voice, responder, invocation, &settings, &url).await
     } else {
-        check_msg(
-            msg.channel_id
-                .say(ctx, "Not in a voice channel to play in")
-                .await,
-        );
+        playback::play(&voice, responder, invocation, &settings, &url).await?;
+        record_play(ctx, invocation.guild_id, invocation.author_id, &url).await;
+        Ok(())
     }
-
-    Ok(())
 }
 
 #[command]
 #[only_in(guilds)]
-pub async fn queue(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let url = match args.single::<String>() {
-        Ok(url) => url,
-        Err(_) => {
-            check_msg(
-                msg.channel_id
-                    .say(ctx, "Must provide a URL to a video or audio")
-                    .await,
-            );
-
-            return Ok(());
-        }
-    };
+pub async fn skip(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    run_skip(ctx, &invocation, &MessageResponder::new(ctx, msg)).await?;
 
-    if !url.starts_with("http") {
-        check_msg(msg.channel_id.say(ctx, "Must provide a valid URL").await);
+    Ok(())
+}
 
-        return Ok(());
-    }
+/// Skips right away for the requester and DJs, and counts a vote for anyone else.
+pub(crate) async fn run_skip(
+    ctx: &Context,
+    invocation: &Invocation,
+    responder: &dyn Responder,
+) -> Result<(), CommandError> {
+    let guild_id = invocation.guild_id;
+    let settings = get_guild_settings(ctx, guild_id).await;
+    let is_dj = is_dj(ctx, invocation, &settings).await;
 
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
+    let votes = get_skip_votes(ctx).await;
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
+    let voice = SongbirdBackend::new(ctx);
+    playback::skip(&voice, responder, invocation, &settings, is_dj, &votes).await
+}
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
+#[command]
+#[only_in(guilds)]
+pub async fn stop(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(msg).context(command_error::NotInGuildSnafu)?;
+    run_stop(ctx, &invocation, &MessageResponder::new(ctx, msg)).await?;
 
-        // Here, we use lazy restartable sources to make sure that we don't pay
-        // for decoding, playback on tracks which aren't actually live yet.
-        let source = match Restartable::ytdl(url, true).await {
-            Ok(source) => source,
-            Err(why) => {
-                println!("Err starting source: {:?}", why);
+    Ok(())
+}
 
-                check_msg(msg.channel_id.say(ctx, "Error sourcing ffmpeg").await);
+pub(crate) async fn run_stop(
+    ctx: &Context,
+    invocation: &Invocation,
+    responder: &dyn Responder,
+) -> Result<(), CommandError> {
+    let settings = get_guild_settings(ctx, invocation.guild_id).await;
+    let is_dj = is_dj(ctx, invocation, &settings).await;
 
-                return Ok(());
-            }
-        };
+    playback::stop(&SongbirdBackend::new(ctx), responder, invocation, is_dj).await
+}
 
-        handler.enqueue_source(source.into());
+/// Whether the author can skip and stop other people's tracks: administrators and members of
+/// the guild's DJ role.
+async fn is_dj(ctx: &Context, invocation: &Invocation, settings: &GuildSettings) -> bool {
+    let member = match invocation.guild_id.member(ctx, invocation.author_id).await {
+        Ok(member) => member,
+        Err(why) => {
+            warn!("Could not fetch member: {:?}", why);
+            return false;
+        }
+    };
 
-        check_msg(
-            msg.channel_id
-                .say(
-                    ctx,
-                    format!("Added song to queue: position {}", handler.queue().len()),
-                )
-                .await,
-        );
-    } else {
-        check_msg(
-            msg.channel_id
-                .say(ctx, "Not in a voice channel to play in")
-                .await,
-        );
-    }
+    let is_admin = member
+        .permissions(ctx)
+        .map(|permissions| permissions.administrator())
+        .unwrap_or(false);
+    let has_role = match settings.dj_role {
+        Some(role_id) => member.roles.contains(&RoleId(role_id)),
+        None => false,
+    };
 
-    Ok(())
+    is_admin || has_role
 }
 
-#[command]
-#[only_in(guilds)]
-pub async fn skip(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx)
+/// Queues every track of a playlist, up to the guild's limit, then sums up what was queued.
+async fn play_playlist(
+    ctx: &Context,
+    voice: &dyn VoiceBackend,
+    responder: &dyn Responder,
+    invocation: &Invocation,
+    settings: &GuildSettings,
+    url: &str,
+) -> Result<(), CommandError> {
+    let guild_id = invocation.guild_id;
+    let limit = settings.max_playlist_tracks;
+
+    let mut progress = invocation
+        .channel_id
+        .say(ctx, "Loading the playlist...")
+        .await;
+
+    // Asking for one more than the limit tells a playlist that was cut short from one that
+    // fits exactly.
+    let config = get_config(ctx).await;
+    let entries = playlist(&config.ytdl_path, url, limit + 1)
         .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
-
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-        let queue = handler.queue();
-        let _ = queue.skip();
+        .context(command_error::PlaylistSnafu)
+        .and_then(|entries| {
+            ensure!(
+                !entries.is_empty(),
+                command_error::BadArgumentSnafu {
+                    reason: "That playlist is empty",
+                }
+            );
+            Ok(entries)
+        });
+    let mut entries = match entries {
+        Ok(entries) => entries,
+        Err(why) => {
+            delete_progress(ctx, progress).await;
+            return Err(why);
+        }
+    };
+    let truncated = entries.len() > limit;
+    entries.truncate(limit);
+
+    let total = entries.len();
+    let mut queued = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        let link = match entry.link() {
+            Some(link) => link,
+            None => continue,
+        };
+        if voice
+            .enqueue_url(guild_id, link, invocation.author_id)
+            .await
+            .is_ok()
+        {
+            queued += 1;
+            record_play(ctx, guild_id, invocation.author_id, link).await;
+        }
 
-        check_msg(
-            msg.channel_id
-                .say(ctx, format!("Song skipped: {} in queue.", queue.len()))
-                .await,
-        );
-    } else {
-        check_msg(
-            msg.channel_id
-                .say(ctx, "Not in a voice channel to play in")
-                .await,
-        );
+        if (i + 1) % PLAYLIST_PROGRESS_STEP == 0 {
+            if let Ok(progress) = &mut progress {
+                let content = format!("Loading the playlist... {}/{}", i + 1, total);
+                check_result(
+                    progress.edit(ctx, |m| m.content(content)).await,
+                    "Error editing message",
+                );
+            }
+        }
     }
+    delete_progress(ctx, progress).await;
 
+    let mut summary = format!("Queued {} of {} tracks from the playlist", queued, total);
+    if truncated {
+        summary.push_str(&format!(", stopping at the limit of {}", limit));
+    }
+    responder.say(summary).await;
     Ok(())
 }
 
-#[command]
-#[only_in(guilds)]
-pub async fn stop(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
+async fn delete_progress(ctx: &Context, progress: serenity::Result<Message>) {
+    if let Ok(progress) = progress {
+        check_result(progress.delete(ctx).await, "Error deleting message");
+    }
+}
 
-    let manager = songbird::get(ctx)
+/// Shows the top search results and waits for the author to reply with the number of one.
+/// Returns its URL.
+async fn pick_search_result(
+    ctx: &Context,
+    invocation: &Invocation,
+    query: &str,
+) -> Result<String, CommandError> {
+    let config = get_config(ctx).await;
+    let results = search(&config.ytdl_path, query, SEARCH_RESULTS)
         .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
-        handler.stop();
-        let queue = handler.queue();
-        queue.stop();
-
-        check_msg(msg.channel_id.say(ctx, "Queue cleared.").await);
-    } else {
-        check_msg(
-            msg.channel_id
-                .say(ctx, "Not in a voice channel to play in")
+        .context(command_error::SearchSnafu)?;
+    let results: Vec<YtdlEntry> = results
+        .into_iter()
+        .filter(|result| result.link().is_some())
+        .collect();
+    ensure!(
+        !results.is_empty(),
+        command_error::BadArgumentSnafu {
+            reason: format!("Nothing found for `{}`", query),
+        }
+    );
+
+    let labels: Vec<String> = results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| {
+            let title = result.title.as_deref().unwrap_or("Untitled");
+            let duration = format_duration(result.duration());
+            // Discord refuses select menu labels over 100 characters.
+            format!("{}. {} ({})", i + 1, truncate(title, 80), duration)
+        })
+        .collect();
+    // A select menu rather than a typed reply, which would need the message content intent.
+    let mut menu = invocation
+        .channel_id
+        .send_message(ctx, |m| {
+            m.content(format!("Results for `{}`", query))
+                .components(|c| {
+                    c.create_action_row(|r| {
+                        r.create_select_menu(|s| {
+                            s.custom_id("search_result")
+                                .placeholder("Pick a result")
+                                .options(|o| {
+                                    for (i, label) in labels.iter().enumerate() {
+                                        o.create_option(|option| option.label(label).value(i));
+                                    }
+                                    o
+                                })
+                        })
+                    })
+                })
+        })
+        .await
+        .context(command_error::DiscordSnafu {
+            action: "send the search results",
+        })?;
+
+    let picked = menu
+        .await_component_interaction(ctx)
+        .author_id(invocation.author_id)
+        .timeout(SEARCH_TIMEOUT)
+        .await;
+
+    // Takes the menu away once used or timed out, so it can't be picked from again.
+    if let Some(interaction) = &picked {
+        check_result(
+            interaction
+                .create_interaction_response(ctx, |r| {
+                    r.kind(InteractionResponseType::DeferredUpdateMessage)
+                })
                 .await,
+            "Error acknowledging search result",
         );
     }
-
-    Ok(())
+    check_result(
+        menu.edit(ctx, |m| m.components(|c| c)).await,
+        "Error removing search results menu",
+    );
+
+    picked
+        .and_then(|interaction| interaction.data.values.first()?.parse::<usize>().ok())
+        .and_then(|index| results.get(index))
+        .and_then(|result| result.link())
+        .map(str::to_string)
+        .context(command_error::NothingPickedSnafu)
 }
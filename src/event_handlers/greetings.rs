@@ -0,0 +1,96 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    model::id::{GuildId, UserId},
+    prelude::{Context, TypeMapKey},
+};
+use tracing::warn;
+
+use crate::{
+    models::guild_settings::GuildSettings, storage::get_user_preferences,
+    utils::parse::get_sound_path,
+};
+
+/// When each member was last greeted in each guild.
+pub struct GreetingCooldownKey;
+
+impl TypeMapKey for GreetingCooldownKey {
+    type Value = Arc<Mutex<HashMap<(GuildId, UserId), Instant>>>;
+}
+
+/// Picks the sound to greet a member with: their personal greeting if they set one and it exists
+/// in the guild, otherwise the guild's welcome sound. Returns `None` when greetings are off for
+/// the guild or the member, when no sound could be found, or when the member was greeted less
+/// than the guild's cooldown ago. The cooldown only starts once a sound was found.
+pub async fn greeting_path(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    settings: &GuildSettings,
+) -> Option<String> {
+    if !settings.greetings_enabled {
+        return None;
+    }
+
+    let preferences = get_user_preferences(ctx, user_id).await;
+    if preferences.greetings_muted {
+        return None;
+    }
+
+    let personal = match &preferences.greeting_sound {
+        Some(sound) => find_sound(ctx, guild_id, sound).await,
+        None => None,
+    };
+    let path = match personal {
+        Some(path) => path,
+        None => find_sound(ctx, guild_id, &settings.welcome_sound).await?,
+    };
+
+    let cooldown = Duration::from_secs(settings.greet_cooldown_seconds);
+    if !start_cooldown(ctx, guild_id, user_id, cooldown).await {
+        return None;
+    }
+
+    Some(path)
+}
+
+async fn find_sound(ctx: &Context, guild_id: GuildId, sound: &str) -> Option<String> {
+    match get_sound_path(ctx, Some(guild_id), sound).await {
+        Ok(path) => Some(path),
+        Err(why) => {
+            warn!("Could not find greeting sound `{}`: {}", sound, why);
+            None
+        }
+    }
+}
+
+/// Records the greeting, unless the member is still in their cooldown.
+async fn start_cooldown(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    cooldown: Duration,
+) -> bool {
+    let cooldowns = {
+        let data = ctx.data.read().await;
+        data.get::<GreetingCooldownKey>()
+            .expect("Greeting cooldowns placed in at initialisation.")
+            .clone()
+    };
+    let mut cooldowns = cooldowns
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let now = Instant::now();
+    match cooldowns.get(&(guild_id, user_id)) {
+        Some(greeted_at) if now.duration_since(*greeted_at) < cooldown => false,
+        _ => {
+            cooldowns.insert((guild_id, user_id), now);
+            true
+        }
+    }
+}
@@ -1,24 +1,72 @@
-use std::{thread, time::Duration};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use serenity::{
     async_trait,
     client::{Context, EventHandler},
-    model::{channel::Message, gateway::Ready, voice::VoiceState},
+    model::{
+        application::interaction::Interaction,
+        channel::Message,
+        gateway::Ready,
+        id::{ChannelId, GuildId},
+        voice::VoiceState,
+    },
 };
 
-use crate::utils::{
-    checks::{check_msg, check_result},
-    diacritics::clean_all,
-    parse::get_sound_path,
+use crate::{
+    command_core::{report_failure, serenity_responder::MessageResponder},
+    commands::slash,
+    event_handlers::greetings::greeting_path,
+    models::{
+        command_error::{command_error, CommandError},
+        guild_settings::GuildSettings,
+    },
+    storage::get_guild_settings,
+    utils::{
+        checks::{check_msg, check_result},
+        diacritics::clean_all,
+        parse::get_sound_path,
+        schedule::schedule_playback,
+        voice::{count_listeners, disconnect},
+    },
 };
-use songbird::{input, tracks::create_player};
+use snafu::prelude::*;
+use songbird::{input, tracks::create_player, Call};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Gives the client of whoever just joined time to connect before the greeting starts.
+const GREETING_DELAY: Duration = Duration::from_millis(1000);
 
-pub struct MainEventHandler;
+#[derive(Default)]
+pub struct MainEventHandler {
+    /// `ready` fires again on every reconnect, but the slash commands only need registering once.
+    slash_registered: AtomicBool,
+}
 
 #[async_trait]
 impl EventHandler for MainEventHandler {
-    async fn ready(&self, _: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!("{} is connected!", ready.user.name);
+
+        if !self.slash_registered.swap(true, Ordering::SeqCst) {
+            slash::register(&ctx).await;
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::ApplicationCommand(command) => slash::run(&ctx, command).await,
+            Interaction::Autocomplete(autocomplete) => {
+                slash::autocomplete(&ctx, autocomplete).await
+            }
+            _ => {}
+        }
     }
 
     async fn voice_state_update(&self, ctx: Context, _old: Option<VoiceState>, _new: VoiceState) {
@@ -27,33 +75,57 @@ impl EventHandler for MainEventHandler {
             .expect("Songbird Voice client placed in at initialisation.")
             .clone();
 
-        let guild = _new.guild_id.unwrap();
-        let channel_id = match _new.channel_id {
-            Some(channel_id) => channel_id,
+        // Voice states outside of guilds come from DM calls, which the bot never joins.
+        let guild = match _new.guild_id {
+            Some(guild) => guild,
             None => return,
         };
 
         if let Some(handler_lock) = manager.get(guild) {
-            let mut handler = handler_lock.lock().await;
-            let current_channel_id = match handler.current_channel() {
-                Some(chan_id) => chan_id,
+            let current_channel_id = match handler_lock.lock().await.current_channel() {
+                Some(chan_id) => ChannelId(chan_id.0),
                 None => {
                     return;
                 }
             };
 
-            if channel_id.0 == current_channel_id.0 {
-                let oldchannel = match _old {
-                    Some(oldstate) => oldstate.channel_id,
-                    None => None,
+            let oldchannel = match _old {
+                Some(oldstate) => oldstate.channel_id,
+                None => None,
+            };
+            let joined = _new.channel_id == Some(current_channel_id)
+                && oldchannel != Some(current_channel_id);
+            let left = oldchannel == Some(current_channel_id)
+                && _new.channel_id != Some(current_channel_id);
+
+            if joined {
+                let settings = get_guild_settings(&ctx, guild).await;
+                let path = match greeting_path(&ctx, guild, _new.user_id, &settings).await {
+                    Some(path) => path,
+                    None => return,
                 };
-                if oldchannel.is_none() || oldchannel.unwrap().0 != channel_id.0 {
-                    let source = input::ffmpeg(get_sound_path("Aloooo.mp3")).await.unwrap();
-                    let (mut audio, _) = create_player(source);
-                    thread::sleep(Duration::from_millis(1000));
-                    audio.set_volume(0.5);
-                    handler.play(audio);
-                    println!("Playing welcome sound...");
+                schedule_sound(handler_lock, path, &settings, GREETING_DELAY);
+                info!("Playing greeting...");
+            } else if left && _new.user_id != ctx.cache.current_user_id() {
+                let settings = get_guild_settings(&ctx, guild).await;
+
+                if count_listeners(&ctx, guild, current_channel_id) == Some(0) {
+                    schedule_idle_disconnect(
+                        ctx,
+                        guild,
+                        current_channel_id,
+                        settings.idle_timeout_seconds,
+                    );
+                } else if let Some(sound) = &settings.farewell_sound {
+                    let path = match get_sound_path(&ctx, Some(guild), sound).await {
+                        Ok(path) => path,
+                        Err(why) => {
+                            warn!("Could not find farewell sound: {}", why);
+                            return;
+                        }
+                    };
+                    schedule_sound(handler_lock, path, &settings, Duration::ZERO);
+                    info!("Playing farewell...");
                 }
             }
         }
@@ -66,27 +138,89 @@ impl EventHandler for MainEventHandler {
                     let without_diacritics =
                         clean_all(_new_message.content.to_lowercase().as_str());
 
-                    if without_diacritics.contains("tacusi") {
+                    let result = if without_diacritics.contains("tacusi") {
                         check_msg(_new_message.channel_id.say(&_ctx, "Gata 😔").await);
 
                         mute(&_ctx, &_new_message).await
                     } else if without_diacritics.contains("glumesc") {
                         check_msg(_new_message.channel_id.say(&_ctx, "😊").await);
 
-                        unmute(&_ctx, &_new_message).await;
+                        unmute(&_ctx, &_new_message).await
+                    } else {
+                        Ok(())
+                    };
+
+                    if let Err(why) = result {
+                        let responder = MessageResponder::new(&_ctx, &_new_message);
+                        report_failure(&responder, "mute", &why).await;
                     }
                 }
             }
             Err(why) => {
-                println!("Err trying to see if message mentions me: {:?}", why);
+                warn!("Err trying to see if message mentions me: {:?}", why);
             }
         };
     }
 }
 
-async fn unmute(ctx: &Context, msg: &Message) {
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
+/// Plays a sound at the guild's volume after `delay`, without keeping the call locked while
+/// waiting.
+fn schedule_sound(
+    handler_lock: Arc<Mutex<Call>>,
+    path: String,
+    settings: &GuildSettings,
+    delay: Duration,
+) {
+    let volume = settings.default_volume as f32 / 100.0;
+
+    schedule_playback(vec![delay], move || {
+        let handler_lock = handler_lock.clone();
+        let path = path.clone();
+        async move {
+            let source = match input::ffmpeg(path).await {
+                Ok(source) => source,
+                Err(why) => {
+                    warn!("Err starting source: {:?}", why);
+                    return;
+                }
+            };
+            let (mut audio, _) = create_player(source);
+            audio.set_volume(volume);
+            handler_lock.lock().await.play(audio);
+        }
+    });
+}
+
+/// Leaves the channel if nobody started listening again before the guild's idle timeout.
+fn schedule_idle_disconnect(ctx: Context, guild_id: GuildId, channel_id: ChannelId, timeout: u64) {
+    if timeout == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(timeout)).await;
+
+        let manager = songbird::get(&ctx)
+            .await
+            .expect("Songbird Voice client placed in at initialisation.")
+            .clone();
+        let still_there = match manager.get(guild_id) {
+            Some(handler_lock) => {
+                let current_channel = handler_lock.lock().await.current_channel();
+                current_channel.map(|chan_id| chan_id.0) == Some(channel_id.0)
+            }
+            None => false,
+        };
+
+        if still_there && count_listeners(&ctx, guild_id, channel_id) == Some(0) {
+            check_result(disconnect(&ctx, guild_id).await, "Err leaving idle channel");
+            info!("Left idle channel in {}", guild_id);
+        }
+    });
+}
+
+async fn unmute(ctx: &Context, msg: &Message) -> Result<(), CommandError> {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
     let manager = songbird::get(ctx)
         .await
         .expect("Songbird Voice client placed in at initialisation.")
@@ -97,11 +231,12 @@ async fn unmute(ctx: &Context, msg: &Message) {
 
         check_result(handler.mute(false).await, "Err when unmuting");
     }
+
+    Ok(())
 }
 
-async fn mute(ctx: &Context, msg: &Message) {
-    let guild = msg.guild(ctx).unwrap();
-    let guild_id = guild.id;
+async fn mute(ctx: &Context, msg: &Message) -> Result<(), CommandError> {
+    let guild_id = msg.guild_id.context(command_error::NotInGuildSnafu)?;
 
     let manager = songbird::get(ctx)
         .await
@@ -111,7 +246,7 @@ async fn mute(ctx: &Context, msg: &Message) {
     let handler_lock = match manager.get(guild_id) {
         Some(handler) => handler,
         None => {
-            return;
+            return Ok(());
         }
     };
 
@@ -120,4 +255,6 @@ async fn mute(ctx: &Context, msg: &Message) {
     if !handler.is_mute() {
         check_result(handler.mute(true).await, "Err when muting");
     }
+
+    Ok(())
 }
@@ -0,0 +1,69 @@
+use serenity::{
+    framework::standard::{macros::hook, CommandResult, DispatchError, Reason},
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    command_core::{report_failure, serenity_responder::MessageResponder},
+    models::{command_error::CommandError, config::get_config},
+    storage::get_guild_settings,
+    utils::logging::record_command,
+};
+
+/// Resolves the prefix for each message, so a guild's `prefix` setting replaces the one from
+/// the config.
+#[hook]
+pub async fn guild_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    let guild_prefix = match msg.guild_id {
+        Some(guild_id) => get_guild_settings(ctx, guild_id).await.command_prefix,
+        None => None,
+    };
+
+    match guild_prefix {
+        Some(prefix) => Some(prefix),
+        None => Some(get_config(ctx).await.command_prefix.clone()),
+    }
+}
+
+/// Names the command in the span `TracedFramework` opened for the message, then logs it.
+#[hook]
+pub async fn before(_: &Context, _: &Message, command_name: &str) -> bool {
+    record_command(command_name);
+    info!("Running command");
+
+    true
+}
+
+/// Answers commands that returned an error.
+#[hook]
+pub async fn after(ctx: &Context, msg: &Message, command_name: &str, result: CommandResult) {
+    if let Err(why) = result {
+        report_failure(&MessageResponder::new(ctx, msg), command_name, &*why).await;
+    }
+}
+
+#[hook]
+pub async fn dispatch_error(
+    ctx: &Context,
+    msg: &Message,
+    error: DispatchError,
+    command_name: &str,
+) {
+    let reason = match error {
+        DispatchError::CheckFailed(_, Reason::User(reason))
+        | DispatchError::CheckFailed(_, Reason::UserAndLog { user: reason, .. }) => reason,
+        DispatchError::LackingPermissions(_) | DispatchError::LackingRole => {
+            "You are not allowed to do that".to_string()
+        }
+        error => {
+            warn!("Could not dispatch {}: {:?}", command_name, error);
+            return;
+        }
+    };
+
+    let error = CommandError::PermissionDenied { reason };
+    report_failure(&MessageResponder::new(ctx, msg), command_name, &error).await;
+}
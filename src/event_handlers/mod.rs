@@ -1 +1,5 @@
+pub mod greetings;
 pub mod handler;
+pub mod hooks;
+pub mod traced_framework;
+pub mod track_loop;
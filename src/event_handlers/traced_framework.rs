@@ -0,0 +1,30 @@
+use serenity::{
+    async_trait,
+    framework::{Framework, StandardFramework},
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::utils::logging::in_message_span;
+
+/// Runs the standard framework inside a span for each message, so everything a command logs
+/// carries the guild, channel, user and command name. The `before` hook names the command once
+/// the framework resolved it.
+pub struct TracedFramework {
+    inner: StandardFramework,
+}
+
+impl TracedFramework {
+    pub fn new(inner: StandardFramework) -> TracedFramework {
+        TracedFramework { inner }
+    }
+}
+
+#[async_trait]
+impl Framework for TracedFramework {
+    async fn dispatch(&self, ctx: Context, msg: Message) {
+        let (guild_id, channel_id, user_id) = (msg.guild_id, msg.channel_id, msg.author.id);
+
+        in_message_span(guild_id, channel_id, user_id, self.inner.dispatch(ctx, msg)).await
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+};
+
+use serenity::{
+    async_trait,
+    model::id::GuildId,
+    prelude::{Context, Mutex, RwLock},
+};
+use songbird::{
+    input::Restartable,
+    tracks::{PlayMode, TrackHandle},
+    Call, Event, EventContext, EventHandler, TrackEvent,
+};
+use tracing::warn;
+
+use crate::models::{
+    loop_mode::{get_loop_modes, LoopMode},
+    track_request::{attach_request, get_request},
+};
+
+type LoopModes = Arc<RwLock<HashMap<GuildId, LoopMode>>>;
+
+/// Applies the guild's loop mode to a queued track: it repeats if the mode is `track` when it
+/// starts, and is queued again once it finishes if the mode is `queue`. The mode is read each
+/// time, so changing it or skipping tracks doesn't need the queue to be touched.
+pub async fn watch_loop(
+    ctx: &Context,
+    guild_id: GuildId,
+    handler_lock: &Arc<Mutex<Call>>,
+    track: &TrackHandle,
+) {
+    let modes = get_loop_modes(ctx).await;
+    add_loop_events(guild_id, Arc::downgrade(handler_lock), modes.clone(), track);
+
+    // `Play` doesn't fire for a track that starts right away in an empty queue.
+    let is_current = handler_lock
+        .lock()
+        .await
+        .queue()
+        .current()
+        .is_some_and(|current| current.uuid() == track.uuid());
+    if is_current && mode_of(&modes, guild_id).await == LoopMode::Track {
+        let _ = track.enable_loop();
+    }
+}
+
+fn add_loop_events(
+    guild_id: GuildId,
+    call: Weak<Mutex<Call>>,
+    modes: LoopModes,
+    track: &TrackHandle,
+) {
+    let _ = track.add_event(
+        Event::Track(TrackEvent::Play),
+        LoopTrack {
+            guild_id,
+            modes: modes.clone(),
+        },
+    );
+    let _ = track.add_event(
+        Event::Track(TrackEvent::End),
+        RequeueTrack {
+            guild_id,
+            call,
+            modes,
+        },
+    );
+}
+
+async fn mode_of(modes: &LoopModes, guild_id: GuildId) -> LoopMode {
+    modes
+        .read()
+        .await
+        .get(&guild_id)
+        .copied()
+        .unwrap_or_default()
+}
+
+struct LoopTrack {
+    guild_id: GuildId,
+    modes: LoopModes,
+}
+
+#[async_trait]
+impl EventHandler for LoopTrack {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(&[(_, track)]) = ctx {
+            if mode_of(&self.modes, self.guild_id).await == LoopMode::Track {
+                let _ = track.enable_loop();
+            }
+        }
+
+        None
+    }
+}
+
+struct RequeueTrack {
+    guild_id: GuildId,
+    call: Weak<Mutex<Call>>,
+    modes: LoopModes,
+}
+
+#[async_trait]
+impl EventHandler for RequeueTrack {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let (state, track) = match ctx {
+            // Skipped, removed and cleared tracks are stopped rather than finished, and stay out.
+            EventContext::Track(&[(state, track)]) if state.playing == PlayMode::End => {
+                (state, track)
+            }
+            _ => return None,
+        };
+        if mode_of(&self.modes, self.guild_id).await != LoopMode::Queue {
+            return None;
+        }
+
+        let (request, handler_lock) = match (get_request(track).await, self.call.upgrade()) {
+            (Some(request), Some(handler_lock)) => (request, handler_lock),
+            _ => return None,
+        };
+
+        let source = match Restartable::ytdl(request.url.clone(), true).await {
+            Ok(source) => source,
+            Err(why) => {
+                warn!("Err requeueing track: {:?}", why);
+                return None;
+            }
+        };
+
+        let requeued = handler_lock.lock().await.enqueue_source(source.into());
+        let _ = requeued.set_volume(state.volume);
+        attach_request(&requeued, request.requester, &request.url).await;
+        add_loop_events(
+            self.guild_id,
+            self.call.clone(),
+            self.modes.clone(),
+            &requeued,
+        );
+
+        None
+    }
+}
@@ -1,19 +1,41 @@
+mod command_core;
 mod commands;
 mod event_handlers;
 mod models;
+mod storage;
 mod utils;
 
-use std::{env, fs::File, io::Read};
+use std::{env, fs::File, io::Read, sync::Arc};
 
 use commands::{
-    essentials::ESSENTIALS_GROUP, funny::FUNNY_GROUP, help::HELP, sounds::SOUNDS_GROUP,
+    essentials::ESSENTIALS_GROUP, funny::FUNNY_GROUP, greetings::GREETINGS_GROUP, help::HELP,
+    library::LIBRARY_GROUP, permissions::PERMISSIONS_GROUP, queue::QUEUE_GROUP,
+    settings::SETTINGS_GROUP, sounds::SOUNDS_GROUP,
+};
+use event_handlers::{
+    greetings::GreetingCooldownKey,
+    handler::MainEventHandler,
+    hooks::{after, before, dispatch_error, guild_prefix},
+    traced_framework::TracedFramework,
+};
+use models::{
+    config::{Config, ConfigKey},
+    config_error::*,
+    loop_mode::LoopModeKey,
+    skip_votes::SkipVotesKey,
+    sound_library::{SoundLibrary, SoundLibraryKey},
+};
+use serenity::{
+    client::Client,
+    framework::StandardFramework,
+    prelude::{GatewayIntents, RwLock},
 };
-use event_handlers::handler::MainEventHandler;
-use models::{config::Config, config_error::*};
-use serenity::{client::Client, framework::StandardFramework, prelude::GatewayIntents};
 
 use snafu::ResultExt;
 use songbird::SerenityInit;
+use storage::{memory::MemoryStorage, sqlite::SqliteStorage, Storage, StorageKey};
+use tracing::{error, warn};
+use utils::logging;
 
 fn read_config() -> Result<Config, ConfigError> {
     let mut config_json_file = File::open("config.json").context(config_error::NotFoundSnafu)?;
@@ -31,42 +53,82 @@ fn read_config() -> Result<Config, ConfigError> {
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
-
     // Configure the client with your Discord bot token in the environment.
-    let config = match read_config() {
-        Ok(cfg) => cfg,
-        Err(why) => {
-            eprintln!(
-                "Could not read config, falling back on default. Reason: {}",
-                why
-            );
-
-            Config {
-                bot_token: env::var("DISCORD_TOKEN").expect("Expected a token in the environment"),
-                command_prefix: ".".to_string(),
-            }
-        }
+    let (config, config_error) = match read_config() {
+        Ok(cfg) => (cfg, None),
+        Err(why) => (
+            Config::with_token(
+                env::var("DISCORD_TOKEN").expect("Expected a token in the environment"),
+            ),
+            Some(why),
+        ),
     };
 
+    // Logging is set up from the config, so a config that couldn't be read is only reported
+    // once it is.
+    logging::init(&config);
+    if let Some(why) = config_error {
+        warn!(
+            "Could not read config, falling back on default. Reason: {}",
+            why
+        );
+    }
+
     let framework = StandardFramework::new()
-        .configure(|c| c.prefix(&config.command_prefix))
+        .configure(|c| c.prefix("").dynamic_prefix(guild_prefix))
         .group(&ESSENTIALS_GROUP)
         .group(&FUNNY_GROUP)
         .group(&SOUNDS_GROUP)
-        .help(&HELP);
+        .group(&QUEUE_GROUP)
+        .group(&LIBRARY_GROUP)
+        .group(&GREETINGS_GROUP)
+        .group(&SETTINGS_GROUP)
+        .group(&PERMISSIONS_GROUP)
+        .help(&HELP)
+        .before(before)
+        .after(after)
+        .on_dispatch_error(dispatch_error);
 
     let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
 
     let mut client = Client::builder(&config.bot_token, intents)
-        .event_handler(MainEventHandler)
-        .framework(framework)
+        .event_handler(MainEventHandler::default())
+        .framework(TracedFramework::new(framework))
         .register_songbird()
         .await
         .expect("Err creating client");
 
+    let sound_library = match SoundLibrary::scan(&config.sound_root).await {
+        Ok(library) => library,
+        Err(why) => {
+            warn!("Could not index sounds, starting empty. Reason: {}", why);
+            SoundLibrary::default()
+        }
+    };
+
+    let storage: Arc<dyn Storage> = match SqliteStorage::open(&config.database_path) {
+        Ok(storage) => Arc::new(storage),
+        Err(why) => {
+            error!(
+                "Could not open database, nothing will be saved. Reason: {}",
+                why
+            );
+            Arc::new(MemoryStorage::new())
+        }
+    };
+
+    {
+        let mut data = client.data.write().await;
+        data.insert::<StorageKey>(storage);
+        data.insert::<SoundLibraryKey>(Arc::new(RwLock::new(sound_library)));
+        data.insert::<ConfigKey>(Arc::new(config));
+        data.insert::<GreetingCooldownKey>(Arc::default());
+        data.insert::<LoopModeKey>(Arc::default());
+        data.insert::<SkipVotesKey>(Arc::default());
+    }
+
     let _ = client
         .start()
         .await
-        .map_err(|why| println!("Client ended: {:?}", why));
+        .map_err(|why| error!("Client ended: {:?}", why));
 }
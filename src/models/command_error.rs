@@ -0,0 +1,77 @@
+use snafu::prelude::*;
+use songbird::{error::JoinError, input::error::Error as InputError, tracks::TrackError};
+
+use crate::models::{
+    sandbox_error::SandboxError, storage_error::StorageError, ytdl_error::YtdlError,
+};
+
+/// Why a command didn't go through. The message is what the user sees, so it says what to do
+/// about it; sources only end up in the log.
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+#[snafu(module)]
+pub enum CommandError {
+    #[snafu(display("Only works in a server"))]
+    NotInGuild,
+    #[snafu(display("This server isn't loaded yet, try again in a moment"))]
+    GuildNotCached,
+    #[snafu(display("{}", usage))]
+    Usage { usage: String },
+    #[snafu(display("{}", reason))]
+    BadArgument { reason: String },
+    #[snafu(display("{}", reason))]
+    PermissionDenied { reason: String },
+    #[snafu(display("{}", source))]
+    Sound { source: SandboxError },
+
+    #[snafu(display("Join a voice channel first"))]
+    AuthorNotInVoice,
+    #[snafu(display("Not in a voice channel"))]
+    NotInVoice,
+    #[snafu(display("Join the voice channel to vote"))]
+    NotListening,
+    #[snafu(display("Error joining the channel"))]
+    Join {
+        #[snafu(source(from(JoinError, Box::new)))]
+        source: Box<JoinError>,
+    },
+    #[snafu(display("Error leaving the channel"))]
+    Leave {
+        #[snafu(source(from(JoinError, Box::new)))]
+        source: Box<JoinError>,
+    },
+    #[snafu(display("Nothing is playing"))]
+    NothingPlaying,
+    #[snafu(display("Could not {} the track", action))]
+    Track { action: String, source: TrackError },
+
+    #[snafu(display("Could not play that sound"))]
+    Source { source: InputError },
+    #[snafu(display("Could not play that sound"))]
+    UnreadableSound,
+    #[snafu(display("yt-dlp could not load that URL"))]
+    Load { source: InputError },
+    #[snafu(display("Could not search, try a URL instead"))]
+    Search { source: YtdlError },
+    #[snafu(display("yt-dlp could not load that playlist"))]
+    Playlist { source: YtdlError },
+    #[snafu(display("Nothing picked"))]
+    NothingPicked,
+
+    #[snafu(display("Could not {}", action))]
+    Storage {
+        action: String,
+        source: StorageError,
+    },
+    #[snafu(display("Could not {}", action))]
+    Io {
+        action: String,
+        source: std::io::Error,
+    },
+    #[snafu(display("Could not {}", action))]
+    Discord {
+        action: String,
+        #[snafu(source(from(serenity::Error, Box::new)))]
+        source: Box<serenity::Error>,
+    },
+}
@@ -1,7 +1,124 @@
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
+use serenity::prelude::{Context, TypeMapKey};
 
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub bot_token: String,
     pub command_prefix: String,
+    /// Folder holding the sound files. Every file name typed in a command must resolve inside it.
+    #[serde(default = "default_sound_root")]
+    pub sound_root: String,
+    /// Play one of the `ilie_*` sounds instead of replying when a sound can't be found.
+    #[serde(default)]
+    pub joke_fallback: bool,
+    /// Role allowed to add, remove and rename sounds, on top of server administrators.
+    #[serde(default)]
+    pub sound_manager_role: Option<u64>,
+    #[serde(default = "default_max_upload_bytes")]
+    pub max_upload_bytes: u64,
+    #[serde(default = "default_max_sound_seconds")]
+    pub max_sound_seconds: u64,
+    /// SQLite database file holding guild settings, sound metadata and play history.
+    #[serde(default = "default_database_path")]
+    pub database_path: String,
+    /// yt-dlp binary used to search and list playlists. Playback itself always runs `yt-dlp`.
+    #[serde(default = "default_ytdl_path")]
+    pub ytdl_path: String,
+    /// Guild to register the slash commands in. Without one they are registered globally.
+    #[serde(default)]
+    pub slash_guild_id: Option<u64>,
+    /// Level or filter directives such as `info,serenity=warn`. `RUST_LOG` wins when it is set.
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    /// File to log to instead of stdout. It is rotated once it grows past `log_file_max_bytes`.
+    #[serde(default)]
+    pub log_file: Option<String>,
+    #[serde(default = "default_log_file_max_bytes")]
+    pub log_file_max_bytes: u64,
+    /// Number of rotated files kept next to the log file, as `<log_file>.1` for the newest.
+    #[serde(default = "default_log_files_kept")]
+    pub log_files_kept: usize,
+}
+
+/// How log lines are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+impl Config {
+    /// Config used when `config.json` is missing: everything but the token is left at its default.
+    pub fn with_token(bot_token: String) -> Config {
+        Config {
+            bot_token,
+            command_prefix: ".".to_string(),
+            sound_root: default_sound_root(),
+            joke_fallback: false,
+            sound_manager_role: None,
+            max_upload_bytes: default_max_upload_bytes(),
+            max_sound_seconds: default_max_sound_seconds(),
+            database_path: default_database_path(),
+            ytdl_path: default_ytdl_path(),
+            slash_guild_id: None,
+            log_level: default_log_level(),
+            log_format: LogFormat::default(),
+            log_file: None,
+            log_file_max_bytes: default_log_file_max_bytes(),
+            log_files_kept: default_log_files_kept(),
+        }
+    }
+}
+
+fn default_sound_root() -> String {
+    "sounds".to_string()
+}
+
+fn default_max_upload_bytes() -> u64 {
+    5 * 1024 * 1024
+}
+
+fn default_max_sound_seconds() -> u64 {
+    30
+}
+
+fn default_database_path() -> String {
+    "freddyfer.db".to_string()
+}
+
+fn default_ytdl_path() -> String {
+    "yt-dlp".to_string()
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_file_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_log_files_kept() -> usize {
+    5
+}
+
+pub struct ConfigKey;
+
+impl TypeMapKey for ConfigKey {
+    type Value = Arc<Config>;
+}
+
+pub async fn get_config(ctx: &Context) -> Arc<Config> {
+    let data = ctx.data.read().await;
+    data.get::<ConfigKey>()
+        .expect("Config placed in at initialisation.")
+        .clone()
 }
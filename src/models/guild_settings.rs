@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::permission_rule::{PermissionRule, RuleSubject};
+
+pub const SETTING_KEYS: [&str; 14] = [
+    "prefix",
+    "greetings",
+    "welcome_sound",
+    "greet_cooldown",
+    "farewell_sound",
+    "auto_join",
+    "idle_timeout",
+    "default_volume",
+    "music_volume",
+    "max_repeat",
+    "max_playlist",
+    "dj_role",
+    "skip_votes",
+    "disabled_groups",
+];
+
+/// Settings a guild admin can change with `.config`. Stored as JSON, so new fields need a serde
+/// default rather than a migration.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    /// Overrides `command_prefix` from the config for this guild.
+    pub command_prefix: Option<String>,
+    /// Whether anyone is greeted when joining the bot's voice channel.
+    pub greetings_enabled: bool,
+    /// Sound played when someone without a personal greeting joins the bot's voice channel.
+    pub welcome_sound: String,
+    /// Seconds before someone who rejoins is greeted again.
+    pub greet_cooldown_seconds: u64,
+    /// Sound played when someone leaves the bot's voice channel, if any.
+    pub farewell_sound: Option<String>,
+    /// Whether playback commands join the author's voice channel when the bot isn't in one.
+    pub auto_join: bool,
+    /// Seconds the bot stays in a channel nobody is listening in. 0 keeps it there.
+    pub idle_timeout_seconds: u64,
+    /// Volume of the welcome sound, in percent.
+    pub default_volume: u32,
+    /// Volume of queued music, in percent. Changed with `.volume`.
+    pub music_volume: u32,
+    /// Highest repeat count accepted by `spam`, `siren` and `ba`.
+    pub max_repeat: i32,
+    /// Most tracks queued from a single playlist URL.
+    pub max_playlist_tracks: usize,
+    /// Role whose members skip and stop anyone's tracks without a vote.
+    pub dj_role: Option<u64>,
+    /// Percent of the listeners that have to vote before someone else's track is skipped.
+    pub skip_vote_percent: u32,
+    /// Lowercase names of the command groups nobody can use in this guild.
+    pub disabled_groups: Vec<String>,
+    /// Who may use which command groups and commands. Managed with `.perm`, not `.config`.
+    pub permission_rules: Vec<PermissionRule>,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        GuildSettings {
+            command_prefix: None,
+            greetings_enabled: true,
+            welcome_sound: "Aloooo.mp3".to_string(),
+            greet_cooldown_seconds: 60,
+            farewell_sound: None,
+            auto_join: true,
+            idle_timeout_seconds: 300,
+            default_volume: 50,
+            music_volume: 100,
+            max_repeat: 49,
+            max_playlist_tracks: 50,
+            dj_role: None,
+            skip_vote_percent: 50,
+            disabled_groups: Vec::new(),
+            permission_rules: Vec::new(),
+        }
+    }
+}
+
+impl GuildSettings {
+    pub fn get(&self, key: &str) -> Result<String, String> {
+        let value = match key {
+            "prefix" => self
+                .command_prefix
+                .clone()
+                .unwrap_or_else(|| "(config default)".to_string()),
+            "greetings" if self.greetings_enabled => "on".to_string(),
+            "greetings" => "off".to_string(),
+            "welcome_sound" => self.welcome_sound.clone(),
+            "greet_cooldown" => format!("{}s", self.greet_cooldown_seconds),
+            "farewell_sound" => self
+                .farewell_sound
+                .clone()
+                .unwrap_or_else(|| "none".to_string()),
+            "auto_join" if self.auto_join => "on".to_string(),
+            "auto_join" => "off".to_string(),
+            "idle_timeout" if self.idle_timeout_seconds == 0 => "never".to_string(),
+            "idle_timeout" => format!("{}s", self.idle_timeout_seconds),
+            "default_volume" => format!("{}%", self.default_volume),
+            "music_volume" => format!("{}%", self.music_volume),
+            "max_repeat" => self.max_repeat.to_string(),
+            "max_playlist" => self.max_playlist_tracks.to_string(),
+            "dj_role" => match self.dj_role {
+                Some(role_id) => format!("<@&{}>", role_id),
+                None => "none".to_string(),
+            },
+            "skip_votes" => format!("{}%", self.skip_vote_percent),
+            "disabled_groups" if self.disabled_groups.is_empty() => "none".to_string(),
+            "disabled_groups" => self.disabled_groups.join(", "),
+            _ => return Err(unknown_key(key)),
+        };
+
+        Ok(value)
+    }
+
+    /// Parses and applies a value typed by an admin. Returns the reason on invalid input.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "prefix" => {
+                if value.is_empty() || value.len() > 5 || value.contains(char::is_whitespace) {
+                    return Err("The prefix must be 1 to 5 characters, without spaces".to_string());
+                }
+                self.command_prefix = Some(value.to_string());
+            }
+            "greetings" => {
+                self.greetings_enabled = match value {
+                    "on" | "true" => true,
+                    "off" | "false" => false,
+                    _ => return Err("Greetings can only be `on` or `off`".to_string()),
+                }
+            }
+            "welcome_sound" => self.welcome_sound = value.to_string(),
+            "greet_cooldown" => {
+                self.greet_cooldown_seconds = match value.trim_end_matches('s').parse::<u64>() {
+                    Ok(seconds) => seconds,
+                    _ => return Err("The cooldown must be a number of seconds".to_string()),
+                }
+            }
+            "farewell_sound" if value == "none" => self.farewell_sound = None,
+            "farewell_sound" => self.farewell_sound = Some(value.to_string()),
+            "auto_join" => {
+                self.auto_join = match value {
+                    "on" | "true" => true,
+                    "off" | "false" => false,
+                    _ => return Err("Auto-join can only be `on` or `off`".to_string()),
+                }
+            }
+            "idle_timeout" if value == "never" => self.idle_timeout_seconds = 0,
+            "idle_timeout" => {
+                self.idle_timeout_seconds = match value.trim_end_matches('s').parse::<u64>() {
+                    Ok(seconds) => seconds,
+                    _ => {
+                        return Err("The timeout must be a number of seconds or `never`".to_string())
+                    }
+                }
+            }
+            "default_volume" => self.default_volume = parse_volume(value)?,
+            "music_volume" => self.music_volume = parse_volume(value)?,
+            "max_repeat" => {
+                self.max_repeat = match value.parse::<i32>() {
+                    Ok(count) if (1..=100).contains(&count) => count,
+                    _ => return Err("The repeat limit must be between 1 and 100".to_string()),
+                }
+            }
+            "max_playlist" => {
+                self.max_playlist_tracks = match value.parse::<usize>() {
+                    Ok(count) if (1..=500).contains(&count) => count,
+                    _ => return Err("The playlist limit must be between 1 and 500".to_string()),
+                }
+            }
+            "dj_role" if value == "none" => self.dj_role = None,
+            "dj_role" => {
+                let id = value.trim_start_matches("<@&").trim_end_matches('>');
+                self.dj_role = match id.parse::<u64>() {
+                    Ok(role_id) => Some(role_id),
+                    _ => {
+                        return Err(
+                            "The DJ role must be a role mention, an ID or `none`".to_string()
+                        )
+                    }
+                }
+            }
+            "skip_votes" => {
+                self.skip_vote_percent = match value.trim_end_matches('%').parse::<u32>() {
+                    Ok(percent) if (1..=100).contains(&percent) => percent,
+                    _ => return Err("The skip votes must be between 1% and 100%".to_string()),
+                }
+            }
+            "disabled_groups" => {
+                self.disabled_groups = value
+                    .split(',')
+                    .map(|group| group.trim().to_lowercase())
+                    .filter(|group| !group.is_empty() && group != "none")
+                    .collect()
+            }
+            _ => return Err(unknown_key(key)),
+        }
+
+        Ok(())
+    }
+
+    pub fn reset(&mut self, key: &str) -> Result<(), String> {
+        let defaults = GuildSettings::default();
+
+        match key {
+            "prefix" => self.command_prefix = defaults.command_prefix,
+            "greetings" => self.greetings_enabled = defaults.greetings_enabled,
+            "welcome_sound" => self.welcome_sound = defaults.welcome_sound,
+            "greet_cooldown" => self.greet_cooldown_seconds = defaults.greet_cooldown_seconds,
+            "farewell_sound" => self.farewell_sound = defaults.farewell_sound,
+            "auto_join" => self.auto_join = defaults.auto_join,
+            "idle_timeout" => self.idle_timeout_seconds = defaults.idle_timeout_seconds,
+            "default_volume" => self.default_volume = defaults.default_volume,
+            "music_volume" => self.music_volume = defaults.music_volume,
+            "max_repeat" => self.max_repeat = defaults.max_repeat,
+            "max_playlist" => self.max_playlist_tracks = defaults.max_playlist_tracks,
+            "dj_role" => self.dj_role = defaults.dj_role,
+            "skip_votes" => self.skip_vote_percent = defaults.skip_vote_percent,
+            "disabled_groups" => self.disabled_groups = defaults.disabled_groups,
+            _ => return Err(unknown_key(key)),
+        }
+
+        Ok(())
+    }
+
+    /// Adds the rule, replacing any earlier one about the same subject and target.
+    pub fn set_rule(&mut self, rule: PermissionRule) {
+        self.remove_rule(rule.subject, &rule.target);
+        self.permission_rules.push(rule);
+    }
+
+    /// Removes the rule about the subject and target. Returns whether there was one.
+    pub fn remove_rule(&mut self, subject: RuleSubject, target: &str) -> bool {
+        let count = self.permission_rules.len();
+        self.permission_rules
+            .retain(|rule| !(rule.subject == subject && rule.target == target));
+
+        self.permission_rules.len() != count
+    }
+
+    pub fn is_group_enabled(&self, group_name: &str) -> bool {
+        !self
+            .disabled_groups
+            .iter()
+            .any(|group| group.eq_ignore_ascii_case(group_name))
+    }
+}
+
+/// Parses a volume in percent, with or without the `%`.
+pub fn parse_volume(value: &str) -> Result<u32, String> {
+    match value.trim_end_matches('%').parse::<u32>() {
+        Ok(volume) if volume <= 200 => Ok(volume),
+        _ => Err("The volume must be between 0 and 200".to_string()),
+    }
+}
+
+fn unknown_key(key: &str) -> String {
+    format!(
+        "Unknown setting `{}`. Settings: {}",
+        key,
+        SETTING_KEYS.join(", ")
+    )
+}
@@ -0,0 +1,64 @@
+use std::{collections::HashMap, fmt, str::FromStr, sync::Arc};
+
+use serenity::{
+    model::id::GuildId,
+    prelude::{Context, RwLock, TypeMapKey},
+};
+
+/// What the queue does when a track ends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopMode {
+    #[default]
+    Off,
+    /// The track that is playing repeats until it is skipped.
+    Track,
+    /// Finished tracks are queued again at the end.
+    Queue,
+}
+
+impl FromStr for LoopMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "off" => Ok(LoopMode::Off),
+            "track" => Ok(LoopMode::Track),
+            "queue" => Ok(LoopMode::Queue),
+            _ => Err("Loop mode can be `track`, `queue` or `off`".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for LoopMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LoopMode::Off => "off",
+            LoopMode::Track => "track",
+            LoopMode::Queue => "queue",
+        };
+        f.write_str(name)
+    }
+}
+
+pub struct LoopModeKey;
+
+impl TypeMapKey for LoopModeKey {
+    type Value = Arc<RwLock<HashMap<GuildId, LoopMode>>>;
+}
+
+pub async fn get_loop_modes(ctx: &Context) -> Arc<RwLock<HashMap<GuildId, LoopMode>>> {
+    let data = ctx.data.read().await;
+    data.get::<LoopModeKey>()
+        .expect("Loop modes placed in at initialisation.")
+        .clone()
+}
+
+pub async fn get_loop_mode(ctx: &Context, guild_id: GuildId) -> LoopMode {
+    get_loop_modes(ctx)
+        .await
+        .read()
+        .await
+        .get(&guild_id)
+        .copied()
+        .unwrap_or_default()
+}
@@ -1,2 +1,15 @@
+pub mod command_error;
 pub mod config;
 pub mod config_error;
+pub mod guild_settings;
+pub mod loop_mode;
+pub mod permission_rule;
+pub mod play_record;
+pub mod sandbox_error;
+pub mod skip_votes;
+pub mod sound_library;
+pub mod sound_metadata;
+pub mod storage_error;
+pub mod track_request;
+pub mod user_preferences;
+pub mod ytdl_error;
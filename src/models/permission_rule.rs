@@ -0,0 +1,114 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Who a permission rule is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleSubject {
+    Everyone,
+    Role(u64),
+    User(u64),
+}
+
+impl fmt::Display for RuleSubject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleSubject::Everyone => f.write_str("everyone"),
+            RuleSubject::Role(role_id) => write!(f, "<@&{}>", role_id),
+            RuleSubject::User(user_id) => write!(f, "<@{}>", user_id),
+        }
+    }
+}
+
+/// Allows or denies a command group or a single command to someone in a guild.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionRule {
+    pub subject: RuleSubject,
+    /// Lowercase name of a command group or of a top-level command.
+    pub target: String,
+    pub allow: bool,
+}
+
+/// Whether the rules let a member use a command. Rules about the user beat rules about their
+/// roles, which beat rules about everyone, and rules about the command beat rules about its
+/// group. When two of the member's roles disagree, the allow wins. Without a matching rule,
+/// everything is allowed.
+pub fn is_allowed(
+    rules: &[PermissionRule],
+    user_id: u64,
+    roles: &[u64],
+    group: &str,
+    command_names: &[&str],
+) -> bool {
+    let mut best: Option<((u8, u8), bool)> = None;
+
+    for rule in rules {
+        let subject_rank = match rule.subject {
+            RuleSubject::User(id) if id == user_id => 2,
+            RuleSubject::Role(id) if roles.contains(&id) => 1,
+            RuleSubject::Everyone => 0,
+            _ => continue,
+        };
+        let target_rank = if command_names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(&rule.target))
+        {
+            1
+        } else if group.eq_ignore_ascii_case(&rule.target) {
+            0
+        } else {
+            continue;
+        };
+
+        let rank = (subject_rank, target_rank);
+        best = match best {
+            Some((best_rank, allowed)) if best_rank > rank => Some((best_rank, allowed)),
+            Some((best_rank, allowed)) if best_rank == rank => Some((rank, allowed || rule.allow)),
+            _ => Some((rank, rule.allow)),
+        };
+    }
+
+    best.is_none_or(|(_, allowed)| allowed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(subject: RuleSubject, target: &str, allow: bool) -> PermissionRule {
+        PermissionRule {
+            subject,
+            target: target.to_string(),
+            allow,
+        }
+    }
+
+    #[test]
+    fn specific_rules_win() {
+        let rules = [
+            rule(RuleSubject::Everyone, "funny", false),
+            rule(RuleSubject::Role(10), "funny", true),
+            rule(RuleSubject::User(2), "ba", false),
+        ];
+
+        assert!(!is_allowed(&rules, 1, &[], "Funny", &["ba"]));
+        assert!(is_allowed(&rules, 1, &[10], "Funny", &["ba"]));
+        assert!(!is_allowed(&rules, 2, &[10], "Funny", &["ba"]));
+        assert!(is_allowed(&rules, 1, &[], "Sounds", &["play"]));
+    }
+
+    #[test]
+    fn command_rules_beat_group_rules() {
+        let rules = [
+            rule(RuleSubject::Everyone, "sounds", true),
+            rule(RuleSubject::Everyone, "spam", false),
+            rule(RuleSubject::Role(10), "spam", false),
+            rule(RuleSubject::Role(11), "spam", true),
+        ];
+
+        assert!(is_allowed(&rules, 1, &[], "Sounds", &["play"]));
+        assert!(!is_allowed(&rules, 1, &[], "Sounds", &["spam"]));
+        assert!(is_allowed(&rules, 1, &[10, 11], "Sounds", &["spam"]));
+    }
+}
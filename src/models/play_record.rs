@@ -0,0 +1,12 @@
+use serenity::model::id::{GuildId, UserId};
+
+/// One entry of a guild's play history.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayRecord {
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    /// Sound name or URL of what was played.
+    pub track: String,
+    /// Unix timestamp, in seconds.
+    pub played_at: i64,
+}
@@ -0,0 +1,25 @@
+use std::path::PathBuf;
+
+use snafu::prelude::*;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+#[snafu(module)]
+pub enum SandboxError {
+    #[snafu(display("No file name given"))]
+    EmptyName,
+    #[snafu(display("`{}` is not a plain file name", name))]
+    InvalidName { name: String },
+    #[snafu(display("Could not find `{}`", name))]
+    NotFound {
+        name: String,
+        source: std::io::Error,
+    },
+    #[snafu(display("Sound root {} is unavailable. Reason: {}", root.display(), source))]
+    RootUnavailable {
+        root: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("`{}` points outside of the sound folder", name))]
+    OutsideRoot { name: String },
+}
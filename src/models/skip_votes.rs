@@ -0,0 +1,74 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use serenity::{
+    model::id::{GuildId, UserId},
+    prelude::{Context, RwLock, TypeMapKey},
+};
+use uuid::Uuid;
+
+/// Listeners who voted to skip the track that is playing. Votes are tied to the track, so they
+/// start over once another one plays.
+#[derive(Clone, Debug, Default)]
+pub struct SkipVotes {
+    track: Option<Uuid>,
+    voters: HashSet<UserId>,
+}
+
+impl SkipVotes {
+    /// Counts the user's vote against the track and returns how many votes it has.
+    pub fn vote(&mut self, track: Uuid, user_id: UserId) -> usize {
+        if self.track != Some(track) {
+            self.track = Some(track);
+            self.voters.clear();
+        }
+        self.voters.insert(user_id);
+
+        self.voters.len()
+    }
+}
+
+/// Votes needed to skip with `listeners` in the channel, when `percent` of them have to agree.
+pub fn votes_needed(listeners: usize, percent: u32) -> usize {
+    (listeners * percent as usize).div_ceil(100).max(1)
+}
+
+pub struct SkipVotesKey;
+
+impl TypeMapKey for SkipVotesKey {
+    type Value = Arc<RwLock<HashMap<GuildId, SkipVotes>>>;
+}
+
+pub async fn get_skip_votes(ctx: &Context) -> Arc<RwLock<HashMap<GuildId, SkipVotes>>> {
+    let data = ctx.data.read().await;
+    data.get::<SkipVotesKey>()
+        .expect("Skip votes placed in at initialisation.")
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn votes_start_over_on_another_track() {
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut votes = SkipVotes::default();
+
+        assert_eq!(votes.vote(first, UserId(1)), 1);
+        assert_eq!(votes.vote(first, UserId(1)), 1);
+        assert_eq!(votes.vote(first, UserId(2)), 2);
+        assert_eq!(votes.vote(second, UserId(2)), 1);
+    }
+
+    #[test]
+    fn rounds_votes_needed_up() {
+        assert_eq!(votes_needed(3, 50), 2);
+        assert_eq!(votes_needed(4, 50), 2);
+        assert_eq!(votes_needed(1, 50), 1);
+        assert_eq!(votes_needed(0, 50), 1);
+        assert_eq!(votes_needed(5, 100), 5);
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use serenity::{
+    model::id::GuildId,
+    prelude::{Context, RwLock, TypeMapKey},
+};
+
+use crate::utils::{
+    diacritics::clean_all, fuzzy::edit_distance, probe::probe_duration, sandbox::resolve_in_root,
+};
+
+const AUDIO_EXTENSIONS: [&str; 6] = ["mp3", "ogg", "wav", "flac", "opus", "m4a"];
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(Clone, Debug)]
+pub struct SoundEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub duration: Option<Duration>,
+    pub format: String,
+    pub tags: Vec<String>,
+    /// Guild namespace the sound belongs to, or `None` for the shared global one.
+    pub guild_id: Option<GuildId>,
+}
+
+impl SoundEntry {
+    /// The file name as it is typed in commands, e.g. `Aloooo.mp3`.
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| format!("{}.{}", self.name, self.format))
+    }
+
+    /// Path of the file relative to the sound root: guild sounds live in a folder named
+    /// after the guild ID.
+    pub fn relative_path(&self) -> String {
+        match self.guild_id {
+            Some(guild_id) => format!("{}/{}", guild_id.0, self.file_name()),
+            None => self.file_name(),
+        }
+    }
+
+    /// Builds the entry for an audio file, or `None` if it is not one.
+    pub async fn index(path: PathBuf, guild_id: Option<GuildId>) -> Option<SoundEntry> {
+        if !path.is_file() {
+            return None;
+        }
+
+        let format = path.extension()?.to_str()?.to_lowercase();
+        if !is_audio_extension(&format) {
+            return None;
+        }
+
+        let name = path.file_stem()?.to_str()?.to_string();
+        let tags = name
+            .split(|c: char| c == '_' || c == '-' || c.is_whitespace())
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        let duration = probe_duration(&path).await;
+
+        Some(SoundEntry {
+            name,
+            path,
+            duration,
+            format,
+            tags,
+            guild_id,
+        })
+    }
+
+    fn matches(&self, folded_term: &str) -> bool {
+        fold(&self.name).contains(folded_term)
+            || self.tags.iter().any(|tag| fold(tag).contains(folded_term))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SoundLibrary {
+    entries: Vec<SoundEntry>,
+    /// Names claimed by uploads and renames that are still writing their file.
+    reserved: HashSet<(Option<GuildId>, String)>,
+}
+
+pub struct SoundLibraryKey;
+
+impl TypeMapKey for SoundLibraryKey {
+    type Value = Arc<RwLock<SoundLibrary>>;
+}
+
+pub async fn get_sound_library(ctx: &Context) -> Arc<RwLock<SoundLibrary>> {
+    let data = ctx.data.read().await;
+    data.get::<SoundLibraryKey>()
+        .expect("Sound library placed in at initialisation.")
+        .clone()
+}
+
+impl SoundLibrary {
+    /// Indexes every audio file directly under `root` as a global sound, and every audio file
+    /// under `root/<guild id>` as a sound of that guild. Each file is probed for its duration.
+    /// Files that resolve outside of `root`, such as escaping symlinks, are skipped.
+    pub async fn scan(root: &str) -> std::io::Result<SoundLibrary> {
+        let root = Path::new(root);
+        let mut entries = scan_folder(root, "", None).await?;
+
+        for dir_entry in std::fs::read_dir(root)? {
+            let dir_entry = dir_entry?;
+            let guild_id = match dir_entry.file_name().to_string_lossy().parse::<u64>() {
+                Ok(guild_id) if dir_entry.path().is_dir() => GuildId(guild_id),
+                _ => continue,
+            };
+
+            let prefix = format!("{}/", guild_id.0);
+            entries.extend(scan_folder(root, &prefix, Some(guild_id)).await?);
+        }
+
+        entries.sort_by_key(|entry| fold(&entry.name));
+
+        Ok(SoundLibrary {
+            entries,
+            reserved: HashSet::new(),
+        })
+    }
+
+    /// Sounds visible from a guild: its own, plus the global ones it doesn't override.
+    /// Without a guild, only the global sounds are visible.
+    pub fn list(&self, guild_id: Option<GuildId>) -> Vec<&SoundEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| match entry.guild_id {
+                Some(_) => entry.guild_id == guild_id,
+                None => !self.is_overridden(guild_id, &entry.name),
+            })
+            .collect()
+    }
+
+    /// Adds an entry, replacing any sound with the same name in the same namespace.
+    pub fn insert(&mut self, entry: SoundEntry) {
+        self.remove(entry.guild_id, &entry.name);
+        self.entries.push(entry);
+        self.entries.sort_by_key(|entry| fold(&entry.name));
+    }
+
+    /// Removes a sound from exactly the given namespace.
+    pub fn remove(&mut self, guild_id: Option<GuildId>, name: &str) -> Option<SoundEntry> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.guild_id == guild_id && entry.name == name)?;
+
+        Some(self.entries.remove(index))
+    }
+
+    /// Claims a name in a namespace while its file is being written, so two uploads or renames
+    /// can't both take it. Returns `false` when it's already claimed.
+    pub fn reserve(&mut self, guild_id: Option<GuildId>, name: &str) -> bool {
+        self.reserved.insert((guild_id, fold(name)))
+    }
+
+    /// Gives up a name claimed with `reserve`.
+    pub fn release(&mut self, guild_id: Option<GuildId>, name: &str) {
+        self.reserved.remove(&(guild_id, fold(name)));
+    }
+
+    /// Looks a sound up ignoring case, diacritics and the file extension. When nothing matches,
+    /// returns the closest names by edit distance as suggestions.
+    pub fn resolve(
+        &self,
+        guild_id: Option<GuildId>,
+        query: &str,
+    ) -> Result<&SoundEntry, Vec<&SoundEntry>> {
+        let folded_query = fold(strip_extension(query));
+        let visible = self.list(guild_id);
+
+        if let Some(entry) = visible
+            .iter()
+            .find(|entry| fold(&entry.name) == folded_query)
+        {
+            return Ok(*entry);
+        }
+
+        let max_distance = (folded_query.chars().count() / 3).max(2);
+        let mut suggestions: Vec<(usize, &SoundEntry)> = visible
+            .into_iter()
+            .map(|entry| (edit_distance(&fold(&entry.name), &folded_query), entry))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        suggestions.sort_by_key(|(distance, _)| *distance);
+
+        Err(suggestions
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    /// Returns the sounds whose name or tags contain `term`, ignoring case and diacritics.
+    pub fn search(&self, guild_id: Option<GuildId>, term: &str) -> Vec<&SoundEntry> {
+        let folded_term = fold(term);
+
+        self.list(guild_id)
+            .into_iter()
+            .filter(|entry| entry.matches(&folded_term))
+            .collect()
+    }
+
+    fn is_overridden(&self, guild_id: Option<GuildId>, name: &str) -> bool {
+        guild_id.is_some()
+            && self
+                .entries
+                .iter()
+                .any(|entry| entry.guild_id == guild_id && fold(&entry.name) == fold(name))
+    }
+}
+
+async fn scan_folder(
+    root: &Path,
+    prefix: &str,
+    guild_id: Option<GuildId>,
+) -> std::io::Result<Vec<SoundEntry>> {
+    let mut entries = Vec::new();
+
+    for dir_entry in std::fs::read_dir(root.join(prefix))? {
+        let relative_path = format!("{}{}", prefix, dir_entry?.file_name().to_string_lossy());
+        if resolve_in_root(root, &relative_path).is_err() {
+            continue;
+        }
+        if let Some(entry) = SoundEntry::index(root.join(relative_path), guild_id).await {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+pub fn is_audio_extension(extension: &str) -> bool {
+    AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+}
+
+fn strip_extension(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if is_audio_extension(extension) => stem,
+        _ => name,
+    }
+}
+
+fn fold(string: &str) -> String {
+    clean_all(string.to_lowercase().as_str())
+}
@@ -0,0 +1,11 @@
+use serenity::model::id::{GuildId, UserId};
+
+/// What the bot remembers about a sound beyond the file itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoundMetadata {
+    pub guild_id: Option<GuildId>,
+    pub name: String,
+    pub uploader_id: Option<UserId>,
+    /// Unix timestamp, in seconds.
+    pub added_at: i64,
+}
@@ -0,0 +1,18 @@
+use snafu::prelude::*;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+#[snafu(module)]
+pub enum StorageError {
+    #[snafu(display("Could not open the database. Reason: {}", source))]
+    Open { source: rusqlite::Error },
+    #[snafu(display("Migration to version {} failed. Reason: {}", version, source))]
+    Migration {
+        version: usize,
+        source: rusqlite::Error,
+    },
+    #[snafu(display("Database query failed. Reason: {}", source))]
+    Query { source: rusqlite::Error },
+    #[snafu(display("Stored settings are not valid JSON. Reason: {}", source))]
+    Json { source: serde_json::Error },
+}
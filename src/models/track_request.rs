@@ -0,0 +1,44 @@
+use serenity::{model::id::UserId, prelude::TypeMapKey};
+use songbird::tracks::TrackHandle;
+
+/// Who asked for a queued track and what it is, kept in the track's own typemap.
+#[derive(Clone, Debug)]
+pub struct TrackRequest {
+    pub requester: UserId,
+    pub url: String,
+    pub title: String,
+}
+
+pub struct TrackRequestKey;
+
+impl TypeMapKey for TrackRequestKey {
+    type Value = TrackRequest;
+}
+
+/// Stores the request alongside the track, titled from the source's metadata if it has any.
+pub async fn attach_request(handle: &TrackHandle, requester: UserId, url: &str) {
+    let title = handle
+        .metadata()
+        .title
+        .clone()
+        .unwrap_or_else(|| url.to_string());
+
+    handle
+        .typemap()
+        .write()
+        .await
+        .insert::<TrackRequestKey>(TrackRequest {
+            requester,
+            url: url.to_string(),
+            title,
+        });
+}
+
+pub async fn get_request(handle: &TrackHandle) -> Option<TrackRequest> {
+    handle
+        .typemap()
+        .read()
+        .await
+        .get::<TrackRequestKey>()
+        .cloned()
+}
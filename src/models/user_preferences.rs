@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Per-user choices that follow a user across guilds. Stored as JSON, like `GuildSettings`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserPreferences {
+    /// Played instead of the guild's welcome sound when the user joins the bot's channel.
+    pub greeting_sound: Option<String>,
+    /// Set by users who don't want to be greeted at all.
+    pub greetings_muted: bool,
+}
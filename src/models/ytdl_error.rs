@@ -0,0 +1,13 @@
+use snafu::prelude::*;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+#[snafu(module)]
+pub enum YtdlError {
+    #[snafu(display("Could not run yt-dlp. Reason: {}", source))]
+    Spawn { source: std::io::Error },
+    #[snafu(display("yt-dlp failed: {}", stderr))]
+    Failed { stderr: String },
+    #[snafu(display("yt-dlp printed invalid JSON. Reason: {}", source))]
+    Json { source: serde_json::Error },
+}
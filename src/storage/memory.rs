@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use serenity::model::id::{GuildId, UserId};
+
+use super::Storage;
+use crate::models::{
+    guild_settings::GuildSettings, play_record::PlayRecord, sound_metadata::SoundMetadata,
+    storage_error::StorageError, user_preferences::UserPreferences,
+};
+
+#[derive(Default)]
+struct Tables {
+    guild_settings: HashMap<GuildId, GuildSettings>,
+    user_preferences: HashMap<UserId, UserPreferences>,
+    sound_metadata: HashMap<(Option<GuildId>, String), SoundMetadata>,
+    play_history: Vec<PlayRecord>,
+}
+
+/// Storage that forgets everything on restart, for tests.
+#[derive(Default)]
+pub struct MemoryStorage {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn guild_settings(&self, guild_id: GuildId) -> Result<GuildSettings, StorageError> {
+        Ok(self
+            .tables()
+            .guild_settings
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn save_guild_settings(
+        &self,
+        guild_id: GuildId,
+        settings: &GuildSettings,
+    ) -> Result<(), StorageError> {
+        self.tables()
+            .guild_settings
+            .insert(guild_id, settings.clone());
+        Ok(())
+    }
+
+    fn user_preferences(&self, user_id: UserId) -> Result<UserPreferences, StorageError> {
+        Ok(self
+            .tables()
+            .user_preferences
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn save_user_preferences(
+        &self,
+        user_id: UserId,
+        preferences: &UserPreferences,
+    ) -> Result<(), StorageError> {
+        self.tables()
+            .user_preferences
+            .insert(user_id, preferences.clone());
+        Ok(())
+    }
+
+    fn sound_metadata(
+        &self,
+        guild_id: Option<GuildId>,
+        name: &str,
+    ) -> Result<Option<SoundMetadata>, StorageError> {
+        Ok(self
+            .tables()
+            .sound_metadata
+            .get(&(guild_id, name.to_string()))
+            .cloned())
+    }
+
+    fn save_sound_metadata(&self, metadata: &SoundMetadata) -> Result<(), StorageError> {
+        self.tables()
+            .sound_metadata
+            .insert((metadata.guild_id, metadata.name.clone()), metadata.clone());
+        Ok(())
+    }
+
+    fn delete_sound_metadata(
+        &self,
+        guild_id: Option<GuildId>,
+        name: &str,
+    ) -> Result<(), StorageError> {
+        self.tables()
+            .sound_metadata
+            .remove(&(guild_id, name.to_string()));
+        Ok(())
+    }
+
+    fn record_play(&self, record: &PlayRecord) -> Result<(), StorageError> {
+        self.tables().play_history.push(record.clone());
+        Ok(())
+    }
+
+    fn play_history(
+        &self,
+        guild_id: GuildId,
+        limit: usize,
+    ) -> Result<Vec<PlayRecord>, StorageError> {
+        let tables = self.tables();
+        let mut records: Vec<PlayRecord> = tables
+            .play_history
+            .iter()
+            .rev()
+            .filter(|record| record.guild_id == guild_id)
+            .cloned()
+            .collect();
+        records.sort_by_key(|record| std::cmp::Reverse(record.played_at));
+        records.truncate(limit);
+
+        Ok(records)
+    }
+}
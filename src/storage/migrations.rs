@@ -0,0 +1,26 @@
+/// Schema changes, applied in order. Settings and preferences are stored as JSON documents so
+/// adding a field to them doesn't need a migration. The number of applied migrations is kept in
+/// SQLite's `user_version`, so new migrations must only ever be appended.
+pub(super) const MIGRATIONS: &[&str] = &["CREATE TABLE guild_settings (
+        guild_id INTEGER PRIMARY KEY,
+        settings TEXT NOT NULL
+    );
+    CREATE TABLE user_preferences (
+        user_id INTEGER PRIMARY KEY,
+        preferences TEXT NOT NULL
+    );
+    CREATE TABLE sound_metadata (
+        guild_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        uploader_id INTEGER,
+        added_at INTEGER NOT NULL,
+        PRIMARY KEY (guild_id, name)
+    );
+    CREATE TABLE play_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        guild_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        track TEXT NOT NULL,
+        played_at INTEGER NOT NULL
+    );
+    CREATE INDEX play_history_guild ON play_history (guild_id, played_at);"];
@@ -0,0 +1,212 @@
+pub mod memory;
+mod migrations;
+pub mod sqlite;
+
+use std::sync::Arc;
+
+use serenity::{
+    model::id::{GuildId, UserId},
+    prelude::{Context, TypeMapKey},
+};
+use tracing::error;
+
+use crate::models::{
+    guild_settings::GuildSettings, play_record::PlayRecord, sound_metadata::SoundMetadata,
+    storage_error::StorageError, user_preferences::UserPreferences,
+};
+
+/// Everything the bot keeps across restarts.
+pub trait Storage: Send + Sync {
+    /// The guild's settings, or the defaults if nobody changed them yet.
+    fn guild_settings(&self, guild_id: GuildId) -> Result<GuildSettings, StorageError>;
+    fn save_guild_settings(
+        &self,
+        guild_id: GuildId,
+        settings: &GuildSettings,
+    ) -> Result<(), StorageError>;
+
+    /// The user's preferences, or the defaults if they never set any.
+    fn user_preferences(&self, user_id: UserId) -> Result<UserPreferences, StorageError>;
+    fn save_user_preferences(
+        &self,
+        user_id: UserId,
+        preferences: &UserPreferences,
+    ) -> Result<(), StorageError>;
+
+    fn sound_metadata(
+        &self,
+        guild_id: Option<GuildId>,
+        name: &str,
+    ) -> Result<Option<SoundMetadata>, StorageError>;
+    fn save_sound_metadata(&self, metadata: &SoundMetadata) -> Result<(), StorageError>;
+    fn delete_sound_metadata(
+        &self,
+        guild_id: Option<GuildId>,
+        name: &str,
+    ) -> Result<(), StorageError>;
+
+    fn record_play(&self, record: &PlayRecord) -> Result<(), StorageError>;
+    /// The latest `limit` plays in a guild, newest first.
+    fn play_history(
+        &self,
+        guild_id: GuildId,
+        limit: usize,
+    ) -> Result<Vec<PlayRecord>, StorageError>;
+}
+
+pub struct StorageKey;
+
+impl TypeMapKey for StorageKey {
+    type Value = Arc<dyn Storage>;
+}
+
+pub async fn get_storage(ctx: &Context) -> Arc<dyn Storage> {
+    let data = ctx.data.read().await;
+    data.get::<StorageKey>()
+        .expect("Storage placed in at initialisation.")
+        .clone()
+}
+
+/// Loads the guild's settings, falling back on the defaults when the database is unavailable.
+pub async fn get_guild_settings(ctx: &Context, guild_id: GuildId) -> GuildSettings {
+    match get_storage(ctx).await.guild_settings(guild_id) {
+        Ok(settings) => settings,
+        Err(why) => {
+            error!("Err loading guild settings: {:?}", why);
+            GuildSettings::default()
+        }
+    }
+}
+
+/// Loads the user's preferences, falling back on the defaults when the database is unavailable.
+pub async fn get_user_preferences(ctx: &Context, user_id: UserId) -> UserPreferences {
+    match get_storage(ctx).await.user_preferences(user_id) {
+        Ok(preferences) => preferences,
+        Err(why) => {
+            error!("Err loading user preferences: {:?}", why);
+            UserPreferences::default()
+        }
+    }
+}
+
+/// Adds an entry to the guild's play history, logging rather than failing the command when
+/// the database is unavailable.
+pub async fn record_play(ctx: &Context, guild_id: GuildId, user_id: UserId, track: &str) {
+    let record = PlayRecord {
+        guild_id,
+        user_id,
+        track: track.to_string(),
+        played_at: now_timestamp(),
+    };
+
+    if let Err(why) = get_storage(ctx).await.record_play(&record) {
+        error!("Err recording play: {:?}", why);
+    }
+}
+
+/// Seconds since the Unix epoch, as stored in the database.
+pub fn now_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{memory::MemoryStorage, sqlite::SqliteStorage, *};
+
+    fn round_trips(storage: &dyn Storage) {
+        let guild = GuildId(1);
+        let user = UserId(2);
+
+        assert_eq!(storage.guild_settings(guild).unwrap().max_repeat, 49);
+        let mut settings = GuildSettings::default();
+        settings.set("prefix", "!").unwrap();
+        settings.set("disabled_groups", "funny, sounds").unwrap();
+        storage.save_guild_settings(guild, &settings).unwrap();
+        let loaded = storage.guild_settings(guild).unwrap();
+        assert_eq!(loaded.command_prefix.as_deref(), Some("!"));
+        assert_eq!(loaded.disabled_groups, ["funny", "sounds"]);
+        assert!(storage
+            .guild_settings(GuildId(3))
+            .unwrap()
+            .command_prefix
+            .is_none());
+
+        let preferences = UserPreferences {
+            greeting_sound: Some("aloooo".to_string()),
+            greetings_muted: false,
+        };
+        storage.save_user_preferences(user, &preferences).unwrap();
+        assert_eq!(storage.user_preferences(user).unwrap(), preferences);
+        assert_eq!(
+            storage.user_preferences(UserId(3)).unwrap(),
+            UserPreferences::default()
+        );
+
+        let metadata = SoundMetadata {
+            guild_id: Some(guild),
+            name: "aloooo".to_string(),
+            uploader_id: Some(user),
+            added_at: 10,
+        };
+        storage.save_sound_metadata(&metadata).unwrap();
+        assert_eq!(
+            storage.sound_metadata(Some(guild), "aloooo").unwrap(),
+            Some(metadata)
+        );
+        assert_eq!(storage.sound_metadata(None, "aloooo").unwrap(), None);
+        storage
+            .delete_sound_metadata(Some(guild), "aloooo")
+            .unwrap();
+        assert_eq!(storage.sound_metadata(Some(guild), "aloooo").unwrap(), None);
+
+        for (track, played_at) in [("first", 1), ("second", 2), ("third", 3)] {
+            storage
+                .record_play(&PlayRecord {
+                    guild_id: guild,
+                    user_id: user,
+                    track: track.to_string(),
+                    played_at,
+                })
+                .unwrap();
+        }
+        let history = storage.play_history(guild, 2).unwrap();
+        let tracks: Vec<&str> = history.iter().map(|record| record.track.as_str()).collect();
+        assert_eq!(tracks, ["third", "second"]);
+        assert!(storage.play_history(GuildId(3), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn memory_storage_round_trips() {
+        round_trips(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn sqlite_storage_round_trips() {
+        round_trips(&SqliteStorage::open(":memory:").unwrap());
+    }
+
+    #[test]
+    fn sqlite_migrations_are_idempotent() {
+        let path =
+            std::env::temp_dir().join(format!("freddyfer-storage-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        SqliteStorage::open(&path)
+            .unwrap()
+            .record_play(&PlayRecord {
+                guild_id: GuildId(1),
+                user_id: UserId(2),
+                track: "aloooo".to_string(),
+                played_at: 1,
+            })
+            .unwrap();
+        let reopened = SqliteStorage::open(&path).unwrap();
+        assert_eq!(reopened.play_history(GuildId(1), 10).unwrap().len(), 1);
+
+        drop(reopened);
+        let _ = std::fs::remove_file(&path);
+    }
+}
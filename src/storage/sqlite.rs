@@ -0,0 +1,233 @@
+use std::{path::Path, sync::Mutex};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serenity::model::id::{GuildId, UserId};
+use snafu::ResultExt;
+
+use super::{migrations::MIGRATIONS, Storage};
+use crate::models::{
+    guild_settings::GuildSettings,
+    play_record::PlayRecord,
+    sound_metadata::SoundMetadata,
+    storage_error::{storage_error, StorageError},
+    user_preferences::UserPreferences,
+};
+
+/// Guild ID stored for global sounds, which have none.
+const GLOBAL_GUILD: i64 = 0;
+
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<SqliteStorage, StorageError> {
+        let connection = Connection::open(path).context(storage_error::OpenSnafu)?;
+        SqliteStorage::migrate(connection)
+    }
+
+    fn migrate(mut connection: Connection) -> Result<SqliteStorage, StorageError> {
+        let applied: usize = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .context(storage_error::QuerySnafu)?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+            let version = index + 1;
+            let transaction = connection
+                .transaction()
+                .context(storage_error::MigrationSnafu { version })?;
+            transaction
+                .execute_batch(migration)
+                .context(storage_error::MigrationSnafu { version })?;
+            transaction
+                .pragma_update(None, "user_version", version)
+                .context(storage_error::MigrationSnafu { version })?;
+            transaction
+                .commit()
+                .context(storage_error::MigrationSnafu { version })?;
+        }
+
+        Ok(SqliteStorage {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn guild_settings(&self, guild_id: GuildId) -> Result<GuildSettings, StorageError> {
+        let json: Option<String> = self
+            .connection()
+            .query_row(
+                "SELECT settings FROM guild_settings WHERE guild_id = ?1",
+                params![guild_id.0 as i64],
+                |row| row.get(0),
+            )
+            .optional()
+            .context(storage_error::QuerySnafu)?;
+
+        match json {
+            Some(json) => serde_json::from_str(&json).context(storage_error::JsonSnafu),
+            None => Ok(GuildSettings::default()),
+        }
+    }
+
+    fn save_guild_settings(
+        &self,
+        guild_id: GuildId,
+        settings: &GuildSettings,
+    ) -> Result<(), StorageError> {
+        let json = serde_json::to_string(settings).context(storage_error::JsonSnafu)?;
+        self.connection()
+            .execute(
+                "INSERT OR REPLACE INTO guild_settings (guild_id, settings) VALUES (?1, ?2)",
+                params![guild_id.0 as i64, json],
+            )
+            .context(storage_error::QuerySnafu)?;
+
+        Ok(())
+    }
+
+    fn user_preferences(&self, user_id: UserId) -> Result<UserPreferences, StorageError> {
+        let json: Option<String> = self
+            .connection()
+            .query_row(
+                "SELECT preferences FROM user_preferences WHERE user_id = ?1",
+                params![user_id.0 as i64],
+                |row| row.get(0),
+            )
+            .optional()
+            .context(storage_error::QuerySnafu)?;
+
+        match json {
+            Some(json) => serde_json::from_str(&json).context(storage_error::JsonSnafu),
+            None => Ok(UserPreferences::default()),
+        }
+    }
+
+    fn save_user_preferences(
+        &self,
+        user_id: UserId,
+        preferences: &UserPreferences,
+    ) -> Result<(), StorageError> {
+        let json = serde_json::to_string(preferences).context(storage_error::JsonSnafu)?;
+        self.connection()
+            .execute(
+                "INSERT OR REPLACE INTO user_preferences (user_id, preferences) VALUES (?1, ?2)",
+                params![user_id.0 as i64, json],
+            )
+            .context(storage_error::QuerySnafu)?;
+
+        Ok(())
+    }
+
+    fn sound_metadata(
+        &self,
+        guild_id: Option<GuildId>,
+        name: &str,
+    ) -> Result<Option<SoundMetadata>, StorageError> {
+        self.connection()
+            .query_row(
+                "SELECT uploader_id, added_at FROM sound_metadata
+                 WHERE guild_id = ?1 AND name = ?2",
+                params![guild_column(guild_id), name],
+                |row| {
+                    Ok(SoundMetadata {
+                        guild_id,
+                        name: name.to_string(),
+                        uploader_id: row.get::<_, Option<i64>>(0)?.map(|id| UserId(id as u64)),
+                        added_at: row.get(1)?,
+                    })
+                },
+            )
+            .optional()
+            .context(storage_error::QuerySnafu)
+    }
+
+    fn save_sound_metadata(&self, metadata: &SoundMetadata) -> Result<(), StorageError> {
+        self.connection()
+            .execute(
+                "INSERT OR REPLACE INTO sound_metadata (guild_id, name, uploader_id, added_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    guild_column(metadata.guild_id),
+                    metadata.name,
+                    metadata.uploader_id.map(|id| id.0 as i64),
+                    metadata.added_at
+                ],
+            )
+            .context(storage_error::QuerySnafu)?;
+
+        Ok(())
+    }
+
+    fn delete_sound_metadata(
+        &self,
+        guild_id: Option<GuildId>,
+        name: &str,
+    ) -> Result<(), StorageError> {
+        self.connection()
+            .execute(
+                "DELETE FROM sound_metadata WHERE guild_id = ?1 AND name = ?2",
+                params![guild_column(guild_id), name],
+            )
+            .context(storage_error::QuerySnafu)?;
+
+        Ok(())
+    }
+
+    fn record_play(&self, record: &PlayRecord) -> Result<(), StorageError> {
+        self.connection()
+            .execute(
+                "INSERT INTO play_history (guild_id, user_id, track, played_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    record.guild_id.0 as i64,
+                    record.user_id.0 as i64,
+                    record.track,
+                    record.played_at
+                ],
+            )
+            .context(storage_error::QuerySnafu)?;
+
+        Ok(())
+    }
+
+    fn play_history(
+        &self,
+        guild_id: GuildId,
+        limit: usize,
+    ) -> Result<Vec<PlayRecord>, StorageError> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare(
+                "SELECT user_id, track, played_at FROM play_history
+                 WHERE guild_id = ?1 ORDER BY played_at DESC, id DESC LIMIT ?2",
+            )
+            .context(storage_error::QuerySnafu)?;
+
+        let records = statement
+            .query_map(params![guild_id.0 as i64, limit as i64], |row| {
+                Ok(PlayRecord {
+                    guild_id,
+                    user_id: UserId(row.get::<_, i64>(0)? as u64),
+                    track: row.get(1)?,
+                    played_at: row.get(2)?,
+                })
+            })
+            .context(storage_error::QuerySnafu)?
+            .collect::<Result<Vec<_>, _>>()
+            .context(storage_error::QuerySnafu)?;
+
+        Ok(records)
+    }
+}
+
+fn guild_column(guild_id: Option<GuildId>) -> i64 {
+    guild_id.map_or(GLOBAL_GUILD, |guild_id| guild_id.0 as i64)
+}
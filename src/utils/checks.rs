@@ -1,14 +1,15 @@
 use serenity::model::prelude::Message;
 use serenity::Result as SerenityResult;
 use std::fmt::Debug;
+use tracing::warn;
 
-/// Checks that a result was successfully received; if not, then logs why to stdout.
+/// Checks that a result was successfully received; if not, then logs why.
 pub fn check_result<T, E>(result: Result<T, E>, error_message: &str)
 where
     E: Debug,
 {
     if let Err(why) = result {
-        println!("{}: {:?}", error_message, why);
+        warn!("{}: {:?}", error_message, why);
     }
 }
 
@@ -0,0 +1,54 @@
+use std::error::Error;
+
+use serenity::{builder::CreateEmbed, utils::Colour};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::models::command_error::CommandError;
+
+/// A failed command as shown to the user: what went wrong, and the ID it was logged under so
+/// it can be found again when they ask for help.
+#[derive(Clone, Debug)]
+pub(crate) struct ErrorReport {
+    pub id: String,
+    pub message: String,
+}
+
+impl ErrorReport {
+    /// Logs the error under a new ID. Anything but a `CommandError` is a bug, so the user only
+    /// gets a generic message for it.
+    pub fn new(command_name: &str, error: &(dyn Error + Send + Sync + 'static)) -> ErrorReport {
+        let id = Uuid::new_v4().to_simple().to_string()[..8].to_string();
+        error!(error_id = %id, "Command {} failed: {:?}", command_name, error);
+
+        let message = match error.downcast_ref::<CommandError>() {
+            Some(error) => error.to_string(),
+            None => "Something went wrong".to_string(),
+        };
+
+        ErrorReport { id, message }
+    }
+
+    pub fn embed<'a>(&self, embed: &'a mut CreateEmbed) -> &'a mut CreateEmbed {
+        embed
+            .colour(Colour::RED)
+            .description(&self.message)
+            .footer(|f| f.text(format!("Error ID: {}", self.id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_command_errors_reach_the_user() {
+        let report = ErrorReport::new("play", &CommandError::NothingPlaying);
+        assert_eq!(report.message, "Nothing is playing");
+        assert_eq!(report.id.len(), 8);
+
+        let io = std::io::Error::other("disk on fire");
+        let report = ErrorReport::new("play", &io);
+        assert_eq!(report.message, "Something went wrong");
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+/// Formats a duration as `m:ss`, or `?:??` when it is unknown.
+pub(crate) fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => {
+            let secs = duration.as_secs();
+            format!("{}:{:02}", secs / 60, secs % 60)
+        }
+        None => "?:??".to_string(),
+    }
+}
+
+const PROGRESS_BAR_WIDTH: usize = 20;
+
+/// Draws how far into a track playback is, as a bar with a marker at the current position.
+pub(crate) fn progress_bar(elapsed: Duration, total: Option<Duration>) -> String {
+    let marker = match total {
+        Some(total) if !total.is_zero() => {
+            let ratio = elapsed.as_secs_f64() / total.as_secs_f64();
+            ((ratio * PROGRESS_BAR_WIDTH as f64) as usize).min(PROGRESS_BAR_WIDTH - 1)
+        }
+        _ => 0,
+    };
+
+    (0..PROGRESS_BAR_WIDTH)
+        .map(|i| if i == marker { '🔘' } else { '▬' })
+        .collect()
+}
+
+/// Cuts `text` down to at most `max_chars` characters, ending it with `…` when it was longer.
+pub(crate) fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
@@ -0,0 +1,34 @@
+use serenity::model::{
+    application::interaction::application_command::ApplicationCommandInteraction,
+    id::{ChannelId, GuildId, UserId},
+    prelude::Message,
+};
+
+/// Who ran a command and where. Prefix and slash commands both build one, so they can share
+/// the code behind each command.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Invocation {
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub author_id: UserId,
+}
+
+impl Invocation {
+    /// `None` for messages outside of a guild.
+    pub fn from_message(msg: &Message) -> Option<Invocation> {
+        Some(Invocation {
+            guild_id: msg.guild_id?,
+            channel_id: msg.channel_id,
+            author_id: msg.author.id,
+        })
+    }
+
+    /// `None` for slash commands used outside of a guild.
+    pub fn from_interaction(command: &ApplicationCommandInteraction) -> Option<Invocation> {
+        Some(Invocation {
+            guild_id: command.guild_id?,
+            channel_id: command.channel_id,
+            author_id: command.user.id,
+        })
+    }
+}
@@ -0,0 +1,272 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use tracing::{field, info_span, warn, Span};
+use tracing_futures::Instrument;
+use tracing_subscriber::{
+    fmt::{writer::BoxMakeWriter, MakeWriter},
+    EnvFilter,
+};
+
+use crate::models::config::{Config, LogFormat};
+
+/// Installs the global subscriber described by the config. A level that doesn't parse falls
+/// back on `info` and a log file that can't be opened on stdout, both with a warning.
+pub(crate) fn init(config: &Config) {
+    let (filter, level_error) = match EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.log_level))
+    {
+        Ok(filter) => (filter, None),
+        Err(why) => (EnvFilter::new("info"), Some(why)),
+    };
+
+    let file = config
+        .log_file
+        .as_ref()
+        .map(|path| RotatingFile::open(path, config.log_file_max_bytes, config.log_files_kept));
+    let (writer, file_error) = match file {
+        Some(Ok(file)) => (BoxMakeWriter::new(file), None),
+        Some(Err(why)) => (BoxMakeWriter::new(io::stdout), Some(why)),
+        None => (BoxMakeWriter::new(io::stdout), None),
+    };
+    // Colours only make sense on a terminal.
+    let ansi = config.log_file.is_none() || file_error.is_some();
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match config.log_format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+
+    if let Some(why) = level_error {
+        warn!(
+            "Invalid log level `{}`, using info. Reason: {}",
+            config.log_level, why
+        );
+    }
+    if let Some(why) = file_error {
+        warn!("Could not open the log file, using stdout. Reason: {}", why);
+    }
+}
+
+/// The span a command runs in, so everything it logs says who ran it and where.
+pub(crate) fn command_span(
+    command: &str,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    user_id: UserId,
+) -> Span {
+    info_span!(
+        "command",
+        command,
+        guild = guild_id.map(|guild_id| guild_id.0),
+        channel = channel_id.0,
+        user = user_id.0
+    )
+}
+
+tokio::task_local! {
+    /// The span `in_message_span` opened, until the framework says which command it is.
+    static MESSAGE_SPAN: Span;
+}
+
+/// Runs the framework's handling of a message in a command span. Which command the message is,
+/// if any, is only known once the framework parsed it, so `record_command` fills that in. A
+/// message that isn't a command logs nothing inside the span.
+pub(crate) async fn in_message_span<F: Future>(
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    user_id: UserId,
+    dispatch: F,
+) -> F::Output {
+    let span = info_span!(
+        "command",
+        command = field::Empty,
+        guild = guild_id.map(|guild_id| guild_id.0),
+        channel = channel_id.0,
+        user = user_id.0
+    );
+
+    MESSAGE_SPAN
+        .scope(span.clone(), dispatch.instrument(span))
+        .await
+}
+
+/// Names the command in the span of the message being handled.
+pub(crate) fn record_command(command: &str) {
+    let _ = MESSAGE_SPAN.try_with(|span| {
+        span.record("command", command);
+    });
+}
+
+/// A log file which is moved aside once it grows past `max_bytes`. The last `kept` files stay
+/// around as `<path>.1`, `<path>.2` and so on, newest first.
+#[derive(Clone)]
+pub(crate) struct RotatingFile {
+    state: Arc<Mutex<RotatingState>>,
+}
+
+struct RotatingState {
+    path: PathBuf,
+    max_bytes: u64,
+    kept: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: impl AsRef<Path>, max_bytes: u64, kept: usize) -> io::Result<RotatingFile> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path)?;
+        let written = file.metadata()?.len();
+
+        Ok(RotatingFile {
+            state: Arc::new(Mutex::new(RotatingState {
+                path,
+                max_bytes,
+                kept,
+                file,
+                written,
+            })),
+        })
+    }
+}
+
+impl RotatingState {
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.kept == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for i in (1..self.kept).rev() {
+                let _ = fs::rename(numbered(&self.path, i), numbered(&self.path, i + 1));
+            }
+            fs::rename(&self.path, numbered(&self.path, 1))?;
+            self.file = open_append(&self.path)?;
+        }
+
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if state.written > 0 && state.written + buf.len() as u64 > state.max_bytes {
+            // Keeps writing to the full file rather than losing lines if it can't be moved.
+            let _ = state.rotate();
+        }
+        let written = state.file.write(buf)?;
+        state.written += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.file.flush()
+    }
+}
+
+impl MakeWriter for RotatingFile {
+    type Writer = RotatingFile;
+
+    fn make_writer(&self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn numbered(path: &Path, number: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", number));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::{dispatcher, info};
+
+    use super::*;
+
+    /// Collects what the subscriber writes, to look at it afterwards.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MakeWriter for Captured {
+        type Writer = Captured;
+
+        fn make_writer(&self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn logs_inside_a_command_carry_its_span() {
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(captured.clone())
+            .with_ansi(false)
+            .finish();
+        let _default = dispatcher::set_default(&subscriber.into());
+
+        // Stands in for the framework: it parses the message, then runs the command.
+        in_message_span(Some(GuildId(1)), ChannelId(2), UserId(3), async {
+            record_command("sound");
+            tokio::task::yield_now().await;
+            info!("Playing a sound");
+        })
+        .await;
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            output.contains(r#"command{guild=1 channel=2 user=3 command="sound"}"#),
+            "{}",
+            output
+        );
+        assert!(output.contains("Playing a sound"));
+    }
+
+    #[test]
+    fn rotates_once_full_and_keeps_the_newest_files() {
+        let dir = std::env::temp_dir().join(format!("freddyfer-logging-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bot.log");
+
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(numbered(&path, 1)).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(numbered(&path, 2)).unwrap(), "second\n");
+        assert!(!numbered(&path, 3).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
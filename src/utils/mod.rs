@@ -1,3 +1,16 @@
 pub(crate) mod checks;
 pub(crate) mod diacritics;
+pub(crate) mod error_report;
+pub(crate) mod format;
+pub(crate) mod fuzzy;
+pub(crate) mod invocation;
+pub(crate) mod logging;
+pub(crate) mod pagination;
 pub(crate) mod parse;
+pub(crate) mod probe;
+pub(crate) mod queue_edit;
+pub(crate) mod sandbox;
+pub(crate) mod schedule;
+pub(crate) mod transcode;
+pub(crate) mod voice;
+pub(crate) mod ytdl;
@@ -0,0 +1,22 @@
+pub(crate) const PAGE_SIZE: usize = 10;
+
+pub(crate) struct Page<'a, T> {
+    pub items: &'a [T],
+    pub number: usize,
+    pub total: usize,
+}
+
+/// Cuts `items` into pages of `PAGE_SIZE` and returns the requested one, 1-based.
+/// Out of range page numbers are clamped to the first or last page.
+pub(crate) fn paginate<T>(items: &[T], page: usize) -> Page<'_, T> {
+    let total = items.len().div_ceil(PAGE_SIZE).max(1);
+    let number = page.clamp(1, total);
+    let offset = (number - 1) * PAGE_SIZE;
+    let end = (offset + PAGE_SIZE).min(items.len());
+
+    Page {
+        items: &items[offset..end],
+        number,
+        total,
+    }
+}
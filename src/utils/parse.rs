@@ -1,26 +1,105 @@
+use std::{path::Path, time::Duration};
+
 use rand::seq::SliceRandom;
-use serenity::framework::standard::Args;
-use std::path::Path;
+use serenity::{model::id::GuildId, prelude::Context};
+use snafu::prelude::*;
+
+use crate::{
+    models::{
+        command_error::{command_error, CommandError},
+        config::get_config,
+        sound_library::{get_sound_library, SoundEntry},
+    },
+    utils::sandbox::{check_file_name, resolve_in_root},
+};
+
+const JOKE_SOUNDS: [&str; 2] = ["ilie_cum", "ilie_ha"];
 
-pub(crate) fn get_repeat_count(mut args: Args, fallback_value: i32) -> i32 {
-    match args.single::<i32>() {
-        Ok(count) => {
-            if 0 < count && count < 50 {
+/// The repeat count to use for the one a user asked for, if any. Counts above the guild's
+/// `max_count` fall back to 1.
+pub(crate) fn get_repeat_count(requested: Option<i32>, fallback_value: i32, max_count: i32) -> i32 {
+    match requested {
+        Some(count) => {
+            if 0 < count && count <= max_count {
                 count
             } else {
                 1
             }
         }
-        Err(_) => fallback_value,
+        None => fallback_value.min(max_count),
     }
 }
 
-pub(crate) fn get_sound_path(sound_name: &str) -> String {
-    let path = format!("sounds/{}", sound_name);
-    if Path::new(path.as_str()).exists() {
-        path
+/// Parses a position in a track written as `ss`, `mm:ss` or `hh:mm:ss`.
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let parts = timestamp
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if parts.is_empty() || parts.len() > 3 || parts[1..].iter().any(|part| *part >= 60) {
+        return None;
+    }
+
+    let seconds = parts.iter().fold(0, |total, part| total * 60 + part);
+    Some(Duration::from_secs(seconds))
+}
+
+/// Resolves a sound name typed by a user to a file path inside the sound root, looking in the
+/// guild's own sounds before the global ones. When there is no such sound, the error lists the
+/// closest names if there are any.
+pub(crate) async fn get_sound_path(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    sound_name: &str,
+) -> Result<String, CommandError> {
+    check_file_name(sound_name).context(command_error::SoundSnafu)?;
+
+    let config = get_config(ctx).await;
+    let library_lock = get_sound_library(ctx).await;
+    let library = library_lock.read().await;
+
+    let suggestions = match library.resolve(guild_id, sound_name) {
+        Ok(entry) => return sandboxed_path(&config.sound_root, entry),
+        Err(suggestions) => suggestions,
+    };
+
+    if config.joke_fallback {
+        let joke = JOKE_SOUNDS.choose(&mut rand::thread_rng());
+        if let Some(Ok(entry)) = joke.map(|joke| library.resolve(guild_id, joke)) {
+            return sandboxed_path(&config.sound_root, entry);
+        }
+    }
+
+    let reason = if suggestions.is_empty() {
+        format!("No sound named `{}`", sound_name)
     } else {
-        let sounds = vec!["sounds/ilie_cum.mp3", "sounds/ilie_ha.mp3"];
-        return sounds.choose(&mut rand::thread_rng()).unwrap().to_string();
+        let names = suggestions
+            .iter()
+            .map(|entry| format!("`{}`", entry.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("No sound named `{}`, did you mean {}?", sound_name, names)
+    };
+    command_error::BadArgumentSnafu { reason }.fail()
+}
+
+fn sandboxed_path(sound_root: &str, entry: &SoundEntry) -> Result<String, CommandError> {
+    resolve_in_root(Path::new(sound_root), &entry.relative_path())
+        .map(|path| path.to_string_lossy().to_string())
+        .context(command_error::SoundSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("45"), Some(Duration::from_secs(45)));
+        assert_eq!(parse_timestamp("1:05"), Some(Duration::from_secs(65)));
+        assert_eq!(parse_timestamp("1:00:30"), Some(Duration::from_secs(3630)));
+        assert_eq!(parse_timestamp("1:75"), None);
+        assert_eq!(parse_timestamp("a:10"), None);
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
     }
 }
@@ -0,0 +1,23 @@
+use std::{path::Path, time::Duration};
+
+use songbird::input::Metadata;
+use tokio::process::Command;
+
+/// Asks ffprobe for the duration of an audio file. Returns `None` if ffprobe is missing or
+/// cannot make sense of the file.
+pub(crate) async fn probe_duration(path: &Path) -> Option<Duration> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "quiet", "-print_format", "json", "-show_format"])
+        .arg(path)
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+
+    Metadata::from_ffprobe_json(&json).duration
+}
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use rand::seq::SliceRandom;
+
+/// Checks that a 1-based position typed by a user points at an upcoming entry. Index 0 is the
+/// entry that is playing, so it can't be moved or removed by position.
+pub(crate) fn check_position<T>(queue: &VecDeque<T>, position: usize) -> Result<usize, String> {
+    match queue.len() {
+        0 | 1 => Err("Nothing else is queued".to_string()),
+        len if position == 0 || position >= len => {
+            Err(format!("Position must be between 1 and {}", len - 1))
+        }
+        _ => Ok(position),
+    }
+}
+
+pub(crate) fn remove_at<T>(queue: &mut VecDeque<T>, position: usize) -> Result<T, String> {
+    let index = check_position(queue, position)?;
+    Ok(queue.remove(index).expect("index checked above"))
+}
+
+pub(crate) fn move_entry<T>(queue: &mut VecDeque<T>, from: usize, to: usize) -> Result<(), String> {
+    check_position(queue, to)?;
+    let entry = remove_at(queue, from)?;
+    queue.insert(to, entry);
+    Ok(())
+}
+
+/// Removes every upcoming entry before `position`, so it plays right after the current one.
+pub(crate) fn drain_before<T>(queue: &mut VecDeque<T>, position: usize) -> Result<Vec<T>, String> {
+    let index = check_position(queue, position)?;
+    Ok(queue.drain(1..index).collect())
+}
+
+/// Removes every entry except the one that is playing.
+pub(crate) fn drain_upcoming<T>(queue: &mut VecDeque<T>) -> Vec<T> {
+    if queue.is_empty() {
+        return Vec::new();
+    }
+    queue.drain(1..).collect()
+}
+
+/// Shuffles the upcoming entries, leaving the one that is playing first.
+pub(crate) fn shuffle_upcoming<T>(queue: &mut VecDeque<T>) -> Result<(), String> {
+    if queue.len() < 2 {
+        return Err("Nothing else is queued".to_string());
+    }
+
+    queue.make_contiguous()[1..].shuffle(&mut rand::thread_rng());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue() -> VecDeque<&'static str> {
+        VecDeque::from(vec!["current", "a", "b", "c"])
+    }
+
+    #[test]
+    fn positions_skip_the_current_entry() {
+        assert_eq!(check_position(&queue(), 1), Ok(1));
+        assert_eq!(check_position(&queue(), 3), Ok(3));
+        assert!(check_position(&queue(), 0).is_err());
+        assert!(check_position(&queue(), 4).is_err());
+        assert!(check_position(&VecDeque::from(vec!["current"]), 1).is_err());
+    }
+
+    #[test]
+    fn removes_and_moves_upcoming_entries() {
+        let mut queue = queue();
+        assert_eq!(remove_at(&mut queue, 2), Ok("b"));
+        assert_eq!(queue, ["current", "a", "c"]);
+
+        let mut queue = self::queue();
+        move_entry(&mut queue, 3, 1).unwrap();
+        assert_eq!(queue, ["current", "c", "a", "b"]);
+        assert!(move_entry(&mut queue, 1, 4).is_err());
+        assert_eq!(queue, ["current", "c", "a", "b"]);
+    }
+
+    #[test]
+    fn drains_keep_the_current_entry() {
+        let mut queue = queue();
+        assert_eq!(drain_before(&mut queue, 3), Ok(vec!["a", "b"]));
+        assert_eq!(queue, ["current", "c"]);
+
+        let mut queue = self::queue();
+        assert_eq!(drain_upcoming(&mut queue), ["a", "b", "c"]);
+        assert_eq!(queue, ["current"]);
+
+        let mut queue = self::queue();
+        shuffle_upcoming(&mut queue).unwrap();
+        assert_eq!(queue[0], "current");
+        assert_eq!(queue.len(), 4);
+    }
+}
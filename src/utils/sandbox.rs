@@ -0,0 +1,176 @@
+use std::path::{Component, Path, PathBuf};
+
+use snafu::{ensure, ResultExt};
+
+use crate::models::sandbox_error::{sandbox_error, SandboxError};
+
+/// Resolves a user supplied file name to a canonical path inside `root`.
+///
+/// Only plain relative names are accepted, and the result is canonicalized so that symlinks
+/// pointing out of `root` are rejected as well.
+pub(crate) fn resolve_in_root(root: &Path, name: &str) -> Result<PathBuf, SandboxError> {
+    check_file_name(name)?;
+
+    let canonical_root = root
+        .canonicalize()
+        .context(sandbox_error::RootUnavailableSnafu { root })?;
+    let canonical_path = root
+        .join(name)
+        .canonicalize()
+        .context(sandbox_error::NotFoundSnafu { name })?;
+
+    ensure!(
+        canonical_path.starts_with(&canonical_root),
+        sandbox_error::OutsideRootSnafu { name }
+    );
+
+    Ok(canonical_path)
+}
+
+/// Rejects anything that is not a plain relative name, without touching the file system.
+pub(crate) fn check_file_name(name: &str) -> Result<(), SandboxError> {
+    ensure!(!name.trim().is_empty(), sandbox_error::EmptyNameSnafu);
+    ensure!(
+        is_plain_relative(name),
+        sandbox_error::InvalidNameSnafu { name }
+    );
+
+    Ok(())
+}
+
+fn is_plain_relative(name: &str) -> bool {
+    if name.starts_with('~') || name.contains('\\') || name.contains('\0') {
+        return false;
+    }
+
+    Path::new(name)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    struct TempRoot {
+        base: PathBuf,
+    }
+
+    impl TempRoot {
+        fn new(test_name: &str) -> TempRoot {
+            let base = std::env::temp_dir().join(format!(
+                "freddyfer-sandbox-{}-{}",
+                test_name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&base);
+            fs::create_dir_all(base.join("sounds/nested")).unwrap();
+            fs::write(base.join("sounds/aloooo.mp3"), b"").unwrap();
+            fs::write(base.join("sounds/nested/ha.ogg"), b"").unwrap();
+            fs::write(base.join("secret.txt"), b"").unwrap();
+
+            TempRoot { base }
+        }
+
+        fn sounds(&self) -> PathBuf {
+            self.base.join("sounds")
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.base);
+        }
+    }
+
+    #[test]
+    fn accepts_files_inside_root() {
+        let root = TempRoot::new("inside");
+
+        let path = resolve_in_root(&root.sounds(), "aloooo.mp3").unwrap();
+        assert!(path.ends_with("sounds/aloooo.mp3"));
+
+        let nested = resolve_in_root(&root.sounds(), "nested/ha.ogg").unwrap();
+        assert!(nested.ends_with("sounds/nested/ha.ogg"));
+    }
+
+    #[test]
+    fn rejects_parent_traversal() {
+        let root = TempRoot::new("traversal");
+
+        for name in [
+            "../secret.txt",
+            "nested/../../secret.txt",
+            "foo/../../etc/passwd",
+            "..",
+        ] {
+            assert!(
+                matches!(
+                    resolve_in_root(&root.sounds(), name),
+                    Err(SandboxError::InvalidName { .. })
+                ),
+                "{} was accepted",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_absolute_and_home_paths() {
+        let root = TempRoot::new("absolute");
+
+        for name in [
+            "/etc/passwd",
+            "~/secret.txt",
+            "\\windows\\system32",
+            "./aloooo.mp3",
+        ] {
+            assert!(
+                matches!(
+                    resolve_in_root(&root.sounds(), name),
+                    Err(SandboxError::InvalidName { .. })
+                ),
+                "{} was accepted",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_empty_and_missing_names() {
+        let root = TempRoot::new("missing");
+
+        assert!(matches!(
+            resolve_in_root(&root.sounds(), "  "),
+            Err(SandboxError::EmptyName)
+        ));
+        assert!(matches!(
+            resolve_in_root(&root.sounds(), "nope.mp3"),
+            Err(SandboxError::NotFound { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_escaping_root() {
+        let root = TempRoot::new("symlink");
+        std::os::unix::fs::symlink(
+            root.base.join("secret.txt"),
+            root.sounds().join("sneaky.mp3"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(
+            root.sounds().join("aloooo.mp3"),
+            root.sounds().join("alias.mp3"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            resolve_in_root(&root.sounds(), "sneaky.mp3"),
+            Err(SandboxError::OutsideRoot { .. })
+        ));
+        assert!(resolve_in_root(&root.sounds(), "alias.mp3").is_ok());
+    }
+}
@@ -0,0 +1,58 @@
+use std::{future::Future, time::Duration};
+
+use tokio::task::JoinHandle;
+
+/// Runs `play` once per delay, waiting each delay before the call, on a separate task so the
+/// caller returns right away. Nothing is held between plays, so `play` should only take the
+/// `Call` lock for as long as it needs it.
+pub(crate) fn schedule_playback<F, Fut>(delays: Vec<Duration>, mut play: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    tokio::spawn(async move {
+        for delay in delays {
+            tokio::time::sleep(delay).await;
+            play().await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::{sync::Mutex, task::yield_now, time::advance};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn other_commands_proceed_during_a_burst() {
+        // Stands in for the songbird `Call`, recording what was played.
+        let call = Arc::new(Mutex::new(Vec::new()));
+
+        let burst_call = call.clone();
+        let mut played = 0;
+        let burst = schedule_playback(vec![Duration::from_millis(50); 5], move || {
+            let call = burst_call.clone();
+            played += 1;
+            let sound = format!("spam {}", played);
+            async move { call.lock().await.push(sound) }
+        });
+
+        // Lets the burst start waiting before the clock moves.
+        yield_now().await;
+        advance(Duration::from_millis(75)).await;
+        yield_now().await;
+        call.try_lock()
+            .expect("the burst should not hold the call between plays")
+            .push("skip".to_string());
+        assert_eq!(call.lock().await.len(), 2);
+        assert!(!burst.is_finished());
+
+        burst.await.unwrap();
+        let played = call.lock().await;
+        assert_eq!(played.len(), 6);
+        assert_eq!(played[1], "skip");
+    }
+}
@@ -0,0 +1,35 @@
+use std::{io, path::Path};
+
+use tokio::process::Command;
+
+/// Converts any audio file ffmpeg understands to the library's standard format: 48kHz stereo
+/// mp3 with loudness normalised, so uploads don't end up much louder than the other sounds.
+pub(crate) async fn normalize_audio(input: &Path, output: &Path) -> io::Result<()> {
+    let result = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(input)
+        .args([
+            "-vn",
+            "-ac",
+            "2",
+            "-ar",
+            "48000",
+            "-af",
+            "loudnorm",
+            "-c:a",
+            "libmp3lame",
+            "-b:a",
+            "128k",
+        ])
+        .arg(output)
+        .output()
+        .await?;
+
+    if result.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(
+            String::from_utf8_lossy(&result.stderr).trim().to_string(),
+        ))
+    }
+}
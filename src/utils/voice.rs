@@ -0,0 +1,44 @@
+use serenity::{
+    model::id::{ChannelId, GuildId},
+    prelude::Context,
+};
+use songbird::error::JoinResult;
+
+/// Number of people in a voice channel, not counting bots. `None` when the guild isn't cached,
+/// which callers should not take as an empty channel.
+pub(crate) fn count_listeners(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Option<usize> {
+    let guild = ctx.cache.guild(guild_id)?;
+
+    let listeners = guild
+        .voice_states
+        .values()
+        .filter(|voice_state| voice_state.channel_id == Some(channel_id))
+        .filter(|voice_state| {
+            !ctx.cache
+                .user(voice_state.user_id)
+                .is_some_and(|user| user.bot)
+        })
+        .count();
+
+    Some(listeners)
+}
+
+/// Stops whatever is queued in the guild and leaves its voice channel, dropping the `Call`.
+pub(crate) async fn disconnect(ctx: &Context, guild_id: GuildId) -> JoinResult<()> {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let mut handler = handler_lock.lock().await;
+        handler.queue().stop();
+        handler.stop();
+    }
+
+    manager.remove(guild_id).await
+}
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use snafu::{ensure, ResultExt};
+use tokio::process::Command;
+
+use crate::models::ytdl_error::{ytdl_error, YtdlError};
+
+/// One entry of yt-dlp's `--flat-playlist` output. Flat entries are listed without resolving
+/// each video, so they are cheap to fetch but only carry a few fields.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub(crate) struct YtdlEntry {
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub webpage_url: Option<String>,
+    pub duration: Option<f64>,
+}
+
+impl YtdlEntry {
+    /// Where the entry can be played from. Older yt-dlp versions list bare video IDs in `url`,
+    /// which aren't usable on their own.
+    pub fn link(&self) -> Option<&str> {
+        self.webpage_url
+            .as_deref()
+            .or(self.url.as_deref())
+            .filter(|link| link.starts_with("http://") || link.starts_with("https://"))
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+            .filter(|seconds| *seconds >= 0.0)
+            .map(Duration::from_secs_f64)
+    }
+}
+
+/// The top `count` results of a YouTube search.
+pub(crate) async fn search(
+    program: &str,
+    query: &str,
+    count: usize,
+) -> Result<Vec<YtdlEntry>, YtdlError> {
+    flat_entries(program, &format!("ytsearch{}:{}", count, query), count).await
+}
+
+/// Whether the URL points at a whole playlist rather than a single track. Videos opened from
+/// a playlist keep their `list=` parameter but are still played on their own.
+pub(crate) fn is_playlist(url: &str) -> bool {
+    url.contains("/playlist?") || (url.contains("soundcloud.com/") && url.contains("/sets/"))
+}
+
+/// The first `limit` entries of a playlist.
+pub(crate) async fn playlist(
+    program: &str,
+    url: &str,
+    limit: usize,
+) -> Result<Vec<YtdlEntry>, YtdlError> {
+    flat_entries(program, url, limit).await
+}
+
+/// Lists up to `limit` entries behind a search or playlist without resolving them.
+async fn flat_entries(
+    program: &str,
+    target: &str,
+    limit: usize,
+) -> Result<Vec<YtdlEntry>, YtdlError> {
+    let output = Command::new(program)
+        .args([
+            "--flat-playlist",
+            "--dump-json",
+            "--no-warnings",
+            "--playlist-end",
+            &limit.to_string(),
+            "--",
+            target,
+        ])
+        .output()
+        .await
+        .context(ytdl_error::SpawnSnafu)?;
+
+    ensure!(
+        output.status.success(),
+        ytdl_error::FailedSnafu {
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+    );
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .take(limit)
+        .map(|line| serde_json::from_str(line).context(ytdl_error::JsonSnafu))
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{os::unix::fs::PermissionsExt, path::PathBuf};
+
+    use super::*;
+
+    /// Writes a shell script standing in for yt-dlp, so the parsing can be tested offline.
+    /// The script sees the same arguments the real binary would. It's written under another name
+    /// and moved into place once closed, so running it can't fail with "text file busy".
+    fn fake_ytdl(name: &str, script: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "freddyfer-fake-ytdl-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let partial_path = path.with_extension("partial");
+        std::fs::write(&partial_path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&partial_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::rename(&partial_path, &path).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn search_reads_flat_entries() {
+        let program = fake_ytdl(
+            "search",
+            r#"[ "$7" = "ytsearch5:never gonna" ] || { echo "unexpected $*" >&2; exit 1; }
+echo '{"title": "First", "url": "https://www.youtube.com/watch?v=a", "duration": 61.0}'
+echo '{"title": "Second", "url": "https://www.youtube.com/watch?v=b", "duration": null}'"#,
+        );
+
+        let results = search(program.to_str().unwrap(), "never gonna", 5)
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title.as_deref(), Some("First"));
+        assert_eq!(results[0].link(), Some("https://www.youtube.com/watch?v=a"));
+        assert_eq!(results[0].duration(), Some(Duration::from_secs(61)));
+        assert_eq!(results[1].duration(), None);
+        let _ = std::fs::remove_file(program);
+    }
+
+    #[tokio::test]
+    async fn playlist_is_capped() {
+        let program = fake_ytdl(
+            "playlist",
+            r#"[ "$4" = "--playlist-end" ] && [ "$5" = "2" ] || { echo "unexpected $*" >&2; exit 1; }
+echo '{"title": "One", "url": "https://www.youtube.com/watch?v=1"}'
+echo '{"title": "Bare", "url": "2"}'
+echo '{"title": "Three", "url": "https://www.youtube.com/watch?v=3"}'"#,
+        );
+
+        let entries = playlist(
+            program.to_str().unwrap(),
+            "https://www.youtube.com/playlist?list=PL1",
+            2,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].link(), Some("https://www.youtube.com/watch?v=1"));
+        assert_eq!(entries[1].link(), None);
+        let _ = std::fs::remove_file(program);
+    }
+
+    #[test]
+    fn detects_playlists() {
+        assert!(is_playlist("https://www.youtube.com/playlist?list=PL1"));
+        assert!(is_playlist("https://soundcloud.com/someone/sets/mix"));
+        assert!(!is_playlist("https://www.youtube.com/watch?v=a&list=PL1"));
+        assert!(!is_playlist("https://soundcloud.com/someone/track"));
+    }
+
+    #[tokio::test]
+    async fn search_reports_failures() {
+        let program = fake_ytdl("failing", "echo 'ERROR: no network' >&2; exit 1");
+
+        match search(program.to_str().unwrap(), "anything", 5).await {
+            Err(YtdlError::Failed { stderr }) => assert_eq!(stderr, "ERROR: no network"),
+            other => panic!("expected a failure, got {:?}", other),
+        }
+        let _ = std::fs::remove_file(program);
+    }
+}